@@ -0,0 +1,89 @@
+use std::fs::File;
+use std::io::{self,Read};
+use std::path::{Path,PathBuf};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize,Ordering};
+use std::thread;
+
+use crate::parse::{file_with_options,ParseOptions,VoFile,E};
+
+#[derive(Debug)]
+pub enum ParseFileError {
+    Io(io::Error),
+    Parse(E)
+}
+
+impl std::fmt::Display for ParseFileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+        match self {
+            ParseFileError::Io(e) => write!(f, "{}", e),
+            ParseFileError::Parse(e) => match e.stuff.first() {
+                Some((_,msg)) => write!(f, "{}", msg),
+                None => write!(f, "Parse error")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseFileError {}
+
+// Reads into buf (reusing its allocation) and parses the whole file
+fn parse_path(path: &Path, options: &ParseOptions, buf: &mut Vec<u8>) -> Result<VoFile,ParseFileError> {
+    buf.clear();
+    File::open(path).and_then(|mut f|f.read_to_end(buf)).map_err(ParseFileError::Io)?;
+    match file_with_options(options, buf) {
+        Ok((_,vo)) => Ok(vo),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(ParseFileError::Parse(e)),
+        Err(nom::Err::Incomplete(_)) => Err(ParseFileError::Parse(E{stuff:vec![(0,"Incomplete input".to_string())]}))
+    }
+}
+
+// Parses each file on a pool of n_threads workers. Results come back in the same order as paths.
+// Shared-pointer memory is Rc-based, so each file is parsed entirely on one worker; what is reused
+// across files is the worker's read buffer.
+pub fn parse_many(paths: &[PathBuf], options: &ParseOptions, n_threads: usize) -> Vec<Result<VoFile,ParseFileError>> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<Result<VoFile,ParseFileError>>>> = Mutex::new(paths.iter().map(|_|None).collect());
+    thread::scope(|s| {
+        for _ in 0..n_threads.clamp(1, paths.len().max(1)) {
+            s.spawn(|| {
+                let mut buf = vec![];
+                loop {
+                    let n = next.fetch_add(1, Ordering::Relaxed);
+                    if n >= paths.len() {
+                        break;
+                    }
+                    let result = parse_path(&paths[n], options, &mut buf);
+                    results.lock().unwrap()[n] = Some(result);
+                }
+            });
+        }
+    });
+    results.into_inner().unwrap().into_iter().map(|r|r.unwrap()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{remove_file,write};
+
+    use super::*;
+
+    #[test]
+    fn results_keep_path_order() {
+        let dir = std::env::temp_dir();
+        let good = dir.join(format!("mathparse-batch-good-{}.vo", std::process::id()));
+        let bad = dir.join(format!("mathparse-batch-bad-{}.vo", std::process::id()));
+        write(&good, &include_bytes!("selftest.vo")[..]).unwrap();
+        write(&bad, b"not a vo file").unwrap();
+        let missing = dir.join("mathparse-batch-missing.vo");
+        let paths = vec![good.clone(), bad.clone(), missing, good.clone()];
+        let results = parse_many(&paths, &ParseOptions::default(), 3);
+        assert_eq!(results.len(), 4);
+        assert_eq!(results[0].as_ref().unwrap().summary.name().to_string(), "Mathparse.Selftest");
+        assert!(matches!(results[1], Err(ParseFileError::Parse(_))));
+        assert!(matches!(results[2], Err(ParseFileError::Io(_))));
+        assert!(results[3].is_ok());
+        remove_file(good).unwrap();
+        remove_file(bad).unwrap();
+    }
+}
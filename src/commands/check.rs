@@ -0,0 +1,219 @@
+use std::fs::{read,write};
+use std::path::{Path,PathBuf};
+use std::process::ExitCode;
+
+use clap::{Args,ValueEnum};
+
+use mathparse::loadpath::{expected_name,LoadPath};
+use mathparse::locate::{locate_corruption,scan_errors};
+use mathparse::native::native_artifact;
+use mathparse::parse::{CodePointerPolicy,file_with_options,marshal_documents,ParseOptions,Utf8Policy,VoFile,E};
+use mathparse::repro::repro;
+use mathparse::types::SummaryDisk;
+
+use super::{fail,GlobalOpts};
+
+#[derive(Debug,Clone,Copy,ValueEnum)]
+pub enum Utf8Arg {
+    Error,
+    Warn,
+    Preserve
+}
+
+#[derive(Debug,Clone,Copy,ValueEnum)]
+pub enum CodePointerArg {
+    Error,
+    Skip,
+    Opaque
+}
+
+#[derive(Debug,Args)]
+pub struct CheckArgs {
+    /// Input .vo file to parse
+    #[arg(value_name = "INPUT", required = true)]
+    pub input: Option<PathBuf>,
+    /// What to do with identifiers that aren't UTF-8
+    #[arg(long, value_enum, default_value_t = Utf8Arg::Error)]
+    pub utf8: Utf8Arg,
+    /// What to do with code pointers (parts of marshalled functions)
+    #[arg(long, value_enum, default_value_t = CodePointerArg::Error)]
+    pub code_pointers: CodePointerArg,
+    /// On failure, save the failing segment as a standalone file
+    #[arg(long, value_name = "FILE")]
+    pub save_repro: Option<PathBuf>,
+    /// When a digest doesn't match, work out which bytes of the segment can have been damaged
+    #[arg(long)]
+    pub locate_corruption: bool,
+    /// Map a directory to a logical prefix, as with coqc -Q, to check the library's recorded name
+    #[arg(short = 'Q', long = "loadpath", num_args = 2, value_names = ["DIR", "PREFIX"])]
+    pub loadpath: Vec<String>,
+    /// Report data after the last segment, and any marshalled values in it, instead of failing
+    #[arg(long)]
+    pub allow_trailing: bool,
+    /// On failure, keep scanning past each bad object and segment and report every problem found
+    #[arg(long)]
+    pub lenient: bool,
+    /// With --lenient, the most problems to report per segment
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub max_errors: usize
+}
+
+fn print_error(contents: &[u8], error: E) {
+    for (pos, msg) in error.stuff {
+        println!("Error {}", msg);
+        let i = &contents[contents.len() - pos..];
+        for byte in &i[..i.len().min(256)] {
+            print!("{:02x} ", byte);
+        }
+        println!();
+    }
+}
+
+fn save_repro(contents: &[u8], error: &E, out: &Path) {
+    match repro(contents, error) {
+        Some(bytes) => match write(out, &bytes) {
+            Ok(()) => println!("Saved failing segment ({} bytes) to {}", bytes.len(), out.display()),
+            Err(e) => println!("Error {}: {}", out.display(), e)
+        },
+        None => println!("Could not find the segment containing the error, no repro saved")
+    }
+}
+
+fn print_scan_errors(contents: &[u8], max_per_segment: usize) {
+    let errors = scan_errors(contents, max_per_segment);
+    for e in &errors {
+        println!("Error segment {} at {:#x}: {}", e.segment, e.offset, e.message);
+    }
+    println!("{} problems found", errors.len());
+}
+
+fn print_corruption(contents: &[u8]) {
+    for c in locate_corruption(contents) {
+        println!("Segment {}: damage somewhere in {:#x}..{:#x} ({})", c.segment, c.range.start, c.range.end, c.reason);
+    }
+}
+
+fn print_trailing(contents: &[u8], rest: &[u8]) {
+    if rest.is_empty() {
+        return;
+    }
+    println!("Warning {} trailing bytes after the last segment, from {:#x}", rest.len(), contents.len() - rest.len());
+    let (documents,unparsed) = marshal_documents(rest);
+    let mut offset = contents.len() - rest.len();
+    for (h,_) in &documents {
+        println!("  marshalled value at {:#x}: {} bytes, {} objects", offset, h.data_len, h.objects);
+        offset += h.header_len + h.data_len as usize;
+    }
+    if !unparsed.is_empty() {
+        println!("  {} bytes that aren't a marshalled value, from {:#x}", unparsed.len(), contents.len() - unparsed.len());
+    }
+}
+
+fn check_name(file_name: &Path, loadpath: &[String], summary: &SummaryDisk) {
+    let loadpaths: Vec<LoadPath> = loadpath.chunks(2).map(|p|LoadPath{dir:PathBuf::from(&p[0]), prefix:p[1].clone()}).collect();
+    if let Some(expected) = expected_name(&loadpaths, file_name) {
+        let actual = summary.name().to_string();
+        if actual != expected {
+            println!("Warning {} records library name {}, but its location means it should be {}", file_name.display(), actual, expected);
+        }
+    }
+}
+
+// Whether the library's native code is where coqc puts it, or None if nothing next to the file was
+// built with -native-compiler (there's no .coq-native directory)
+fn check_native(file_name: &Path, summary: &SummaryDisk) -> Option<bool> {
+    let native = native_artifact(file_name, summary.name());
+    if !native.parent()?.is_dir() {
+        return None;
+    }
+    if native.exists() {
+        info!("Native artifact found: {}", native.display());
+    } else {
+        println!("Warning {}: native code {} is missing", file_name.display(), native.display());
+    }
+    Some(native.exists())
+}
+
+// One line at -v, plus a line per segment at -vv
+fn print_summary(verbosity: u8, contents: &[u8], vo: &VoFile, bad_digests: &[usize], native: Option<bool>) {
+    if verbosity < 1 {
+        return;
+    }
+    println!("{}: Coq {}, {} segments, checksums {}{}",
+        vo.summary.name(),
+        vo.coq_version(),
+        vo.segments.len(),
+        if bad_digests.is_empty() { "verified" } else { "MISMATCHED" },
+        match native {
+            Some(true) => ", native code present",
+            Some(false) => ", native code MISSING",
+            None => ""
+        });
+    if verbosity < 2 {
+        return;
+    }
+    for (n,segment) in vo.segments.iter().enumerate() {
+        println!("  segment {} ({}): {} bytes at {:#x}..{:#x}, digest {} {}",
+            n,
+            vo.segment_name(n).unwrap_or("?"),
+            segment.len,
+            segment.stop - segment.len,
+            segment.stop,
+            segment.digest.to_hex(),
+            if segment.digest_ok(contents) { "ok" } else { "MISMATCH" });
+    }
+}
+
+pub fn run(global: &GlobalOpts, args: &CheckArgs) -> ExitCode {
+    let file_name = args.input.as_deref().unwrap();
+    let file_contents = match read(file_name) {
+        Ok(contents) => contents,
+        Err(e) => return fail(file_name, e)
+    };
+    let utf8 = match args.utf8 {
+        Utf8Arg::Error => Utf8Policy::Error,
+        Utf8Arg::Warn => Utf8Policy::Warn,
+        Utf8Arg::Preserve => Utf8Policy::Preserve
+    };
+    let code_pointers = match args.code_pointers {
+        CodePointerArg::Error => CodePointerPolicy::Error,
+        CodePointerArg::Skip => CodePointerPolicy::Skip,
+        CodePointerArg::Opaque => CodePointerPolicy::Opaque
+    };
+    let options = ParseOptions{utf8, code_pointers, allow_trailing: args.allow_trailing, ..ParseOptions::default()};
+
+    match file_with_options(&options, &file_contents) {
+        Ok((rest,vo)) => {
+            print_trailing(&file_contents, rest);
+            let bad = vo.bad_digests(&file_contents);
+            for n in &bad {
+                println!("Error segment {}: digest mismatch", n);
+            }
+            if !bad.is_empty() && args.locate_corruption {
+                print_corruption(&file_contents);
+            }
+            let native = check_native(file_name, &vo.summary);
+            print_summary(global.verbosity, &file_contents, &vo, &bad, native);
+            check_name(file_name, &args.loadpath, &vo.summary);
+            if bad.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+        }
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            if let Some(out) = &args.save_repro {
+                save_repro(&file_contents, &e, out);
+            }
+            if args.lenient {
+                print_scan_errors(&file_contents, args.max_errors);
+            } else {
+                print_error(&file_contents, e);
+            }
+            if args.locate_corruption {
+                print_corruption(&file_contents);
+            }
+            ExitCode::FAILURE
+        }
+        Err(nom::Err::Incomplete(_)) => {
+            println!("Error {}: unexpected end of file", file_name.display());
+            ExitCode::FAILURE
+        }
+    }
+}
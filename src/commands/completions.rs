@@ -0,0 +1,21 @@
+use std::io::stdout;
+use std::process::ExitCode;
+
+use clap::{Args,CommandFactory};
+use clap_complete::{generate,Shell};
+
+use crate::Cli;
+
+use super::GlobalOpts;
+
+#[derive(Debug,Args)]
+pub struct CompletionsArgs {
+    /// Shell to generate the completion script for
+    #[arg(value_enum)]
+    pub shell: Shell
+}
+
+pub fn run(_global: &GlobalOpts, args: &CompletionsArgs) -> ExitCode {
+    generate(args.shell, &mut Cli::command(), "mathparse", &mut stdout());
+    ExitCode::SUCCESS
+}
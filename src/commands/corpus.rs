@@ -0,0 +1,83 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::corpus::{Corpus,run_corpus};
+
+use super::{fail,output,read_json,write_json,GlobalOpts,print_report,stdout_failed};
+
+#[derive(Debug,Args)]
+pub struct CorpusArgs {
+    /// Directory containing the corpus
+    pub dir: PathBuf,
+    /// results.json to compare against
+    #[arg(long)]
+    pub expect: Option<PathBuf>,
+    /// Write the results to this file, for use with --expect
+    #[arg(long)]
+    pub record: Option<PathBuf>,
+    /// List every failing file instead of one line per distinct error
+    #[arg(long)]
+    pub verbose_errors: bool
+}
+
+fn at(offset: Option<usize>) -> String {
+    offset.map(|o|format!(" @{:#x}", o)).unwrap_or_default()
+}
+
+fn print_error_groups(corpus: &Corpus, verbose: bool) {
+    for group in corpus.error_groups() {
+        let (first,offset) = &group.files[0];
+        if verbose {
+            println!("{} in {} files:", group.message, group.files.len());
+            for (file,offset) in &group.files {
+                println!("    {}{}", file, at(*offset));
+            }
+        } else if group.files.len() == 1 {
+            println!("{} in {}{}", group.message, first, at(*offset));
+        } else {
+            println!("{} in {} files (first: {}{})", group.message, group.files.len(), first, at(*offset));
+        }
+    }
+}
+
+pub fn run(global: &GlobalOpts, args: &CorpusArgs) -> ExitCode {
+    let corpus = match run_corpus(&args.dir) {
+        Ok(corpus) => corpus,
+        Err(e) => return fail(&args.dir, e)
+    };
+    if let Some(record) = &args.record {
+        if let Err(e) = output(Some(record)).and_then(|mut out|write_json(&mut out, &corpus)) {
+            return fail(record, e);
+        }
+    }
+    if let Some(expect) = &args.expect {
+        let expected: Corpus = match read_json(expect) {
+            Ok(expected) => expected,
+            Err(e) => return fail(expect, e)
+        };
+        let comparison = corpus.compare(&expected);
+        if global.json() {
+            if let Err(e) = print_report(&comparison) {
+                return stdout_failed(e);
+            }
+        } else {
+            for diff in &comparison.differences {
+                println!("{}", diff);
+            }
+            println!("{} files, {} as expected, {} differences", comparison.total, comparison.as_expected, comparison.differences.len());
+        }
+        if comparison.differences.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+    } else if global.json() {
+        if let Err(e) = print_report(&corpus) {
+            return stdout_failed(e);
+        }
+        ExitCode::SUCCESS
+    } else {
+        print_error_groups(&corpus, args.verbose_errors);
+        let ok = corpus.results.iter().filter(|r|r.ok).count();
+        println!("{} files, {} parsed, {} failed", corpus.results.len(), ok, corpus.results.len() - ok);
+        ExitCode::SUCCESS
+    }
+}
@@ -0,0 +1,54 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::diff::{diff_dirs,Change};
+
+use super::{GlobalOpts,print_report,stdout_failed};
+
+#[derive(Debug,Args)]
+pub struct DiffArgs {
+    /// Directory with the old build
+    pub old: PathBuf,
+    /// Directory with the new build
+    pub new: PathBuf,
+    /// Also list libraries that didn't change
+    #[arg(long)]
+    pub all: bool
+}
+
+pub fn run(global: &GlobalOpts, args: &DiffArgs) -> ExitCode {
+    let mut diffs = match diff_dirs(&args.old, &args.new) {
+        Ok(diffs) => diffs,
+        Err(e) => {
+            println!("Error comparing {} with {}: {}", args.old.display(), args.new.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if !args.all {
+        diffs.retain(|d|d.change != Change::Unchanged);
+    }
+    if global.json() {
+        if let Err(e) = print_report(&diffs) {
+            return stdout_failed(e);
+        }
+    } else {
+        for d in &diffs {
+            let change = match d.change {
+                Change::Added => "added",
+                Change::Removed => "removed",
+                Change::Unchanged => "unchanged",
+                Change::OpaqueOnly => "opaque proofs only",
+                Change::Metadata => "metadata only",
+                Change::Digest => "library digest"
+            };
+            if d.segments.is_empty() {
+                println!("{}: {}", d.name, change);
+            } else {
+                println!("{}: {} ({})", d.name, change, d.segments.join(", "));
+            }
+        }
+    }
+    ExitCode::SUCCESS
+}
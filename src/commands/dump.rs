@@ -0,0 +1,74 @@
+use std::fs::read;
+use std::io::{BufWriter,Write};
+use std::path::{Path,PathBuf};
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::dump::{dump_json,DumpLimits};
+use mathparse::parse::marshal_data;
+use mathparse::split::split_segments;
+
+use super::{fail,output,segment_number,GlobalOpts};
+
+#[derive(Debug,Args)]
+pub struct DumpArgs {
+    /// Input .vo file
+    pub input: PathBuf,
+    /// Segment to dump, by number or by name (e.g. library)
+    #[arg(default_value = "0")]
+    pub segment: String,
+    /// Output file (default: stdout)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Summarizes blocks nested this deep instead of writing them (the segment's value is at depth 0)
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
+    /// Summarizes the rest of the value once this many nodes have been written
+    #[arg(long, value_name = "N")]
+    pub max_nodes: Option<usize>
+}
+
+pub fn run(_global: &GlobalOpts, args: &DumpArgs) -> ExitCode {
+    let contents = match read(&args.input) {
+        Ok(contents) => contents,
+        Err(e) => return fail(&args.input, e)
+    };
+    let (magic,segments) = match split_segments(&contents) {
+        Some(s) => s,
+        None => {
+            println!("Error file too short for a .vo");
+            return ExitCode::FAILURE;
+        }
+    };
+    let n = match segment_number(magic, &args.segment) {
+        Some(n) => n,
+        None => {
+            println!("Error no segment called {}", args.segment);
+            return ExitCode::FAILURE;
+        }
+    };
+    let data = match segments.get(n).map(|m|marshal_data(m)) {
+        Some(Some((_,data))) => data,
+        Some(None) => {
+            println!("Error segment {}: bad marshal header", n);
+            return ExitCode::FAILURE;
+        }
+        None => {
+            println!("Error no segment {}", n);
+            return ExitCode::FAILURE;
+        }
+    };
+    let limits = DumpLimits{max_depth: args.max_depth, max_nodes: args.max_nodes};
+    let mut out = match output(args.output.as_deref()) {
+        Ok(out) => BufWriter::new(out),
+        Err(e) => return fail(args.output.as_deref().unwrap_or(Path::new("-")), e)
+    };
+    match dump_json(&data, &limits, &mut out).and_then(|_|out.flush()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            println!("Error segment {}: {}", n, e);
+            ExitCode::FAILURE
+        }
+    }
+}
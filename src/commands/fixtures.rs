@@ -0,0 +1,62 @@
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::fixtures::{coqc_version,find_coqcs,fixtures,generate,Coqc};
+
+use super::{fail,GlobalOpts};
+
+#[derive(Debug,Args)]
+pub struct FixturesArgs {
+    /// Use this coqc instead of searching PATH for coqc and coqc-<version>
+    #[arg(long, value_name = "COQC")]
+    pub coqc: Vec<PathBuf>,
+    /// Directory of .v sources
+    #[arg(long, default_value = "tests/fixtures/src")]
+    pub sources: PathBuf,
+    /// Directory to write <version>/*.vo and <version>/expected.json under
+    #[arg(long, default_value = "tests/fixtures/vo")]
+    pub out: PathBuf
+}
+
+pub fn run(_global: &GlobalOpts, args: &FixturesArgs) -> ExitCode {
+    let coqcs: Vec<Coqc> = if args.coqc.is_empty() {
+        find_coqcs(&env::var_os("PATH").unwrap_or_default())
+    } else {
+        let mut coqcs = vec![];
+        for path in &args.coqc {
+            match coqc_version(path) {
+                Ok(version) => coqcs.push(Coqc{path:path.clone(), version}),
+                Err(e) => {
+                    println!("Error {}", e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        coqcs
+    };
+    if coqcs.is_empty() {
+        println!("Error no coqc found on PATH");
+        return ExitCode::FAILURE;
+    }
+    let fixtures = match fixtures(&args.sources) {
+        Ok(fixtures) => fixtures,
+        Err(e) => return fail(&args.sources, e)
+    };
+    let mut status = ExitCode::SUCCESS;
+    for coqc in &coqcs {
+        match generate(coqc, &fixtures, &args.out) {
+            Ok(generated) => {
+                println!("Coq {} ({}): {} compiled, {} skipped",
+                    coqc.version, coqc.path.display(), generated.compiled.len(), generated.skipped.len());
+            }
+            Err(e) => {
+                println!("Error {}", e);
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
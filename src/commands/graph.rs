@@ -0,0 +1,39 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::graph::graph_metrics;
+use mathparse::index::build_index;
+
+use super::{fail,GlobalOpts,print_report,stdout_failed};
+
+#[derive(Debug,Args)]
+pub struct GraphArgs {
+    /// Root directory of the development
+    pub dir: PathBuf
+}
+
+pub fn run(global: &GlobalOpts, args: &GraphArgs) -> ExitCode {
+    let index = match build_index(&args.dir) {
+        Ok(index) => index,
+        Err(e) => return fail(&args.dir, e)
+    };
+    let metrics = graph_metrics(&index);
+    if global.json() {
+        if let Err(e) = print_report(&metrics) {
+            return stdout_failed(e);
+        }
+        return ExitCode::SUCCESS;
+    }
+    println!("{:<40} {:>5} {:>5}", "library", "in", "out");
+    for lib in &metrics.libraries {
+        println!("{:<40} {:>5} {:>5}", lib.name, lib.in_degree, lib.out_degree);
+    }
+    println!("longest chain ({}): {}", metrics.longest_chain.len(), metrics.longest_chain.join(" -> "));
+    for cycle in &metrics.cycles {
+        println!("cycle: {}", cycle.join(", "));
+    }
+    println!("nothing depends on: {}", metrics.unused.join(", "));
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,64 @@
+use std::path::{Path,PathBuf};
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::index::{Index,build_index,update_index};
+
+use super::{fail,output,read_json,write_json,GlobalOpts};
+
+#[derive(Debug,Args)]
+pub struct IndexArgs {
+    /// Root directory of the development
+    pub dir: PathBuf,
+    /// Output file (default: stdout)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// Previous index.json; only changed files are reparsed
+    #[arg(short, long)]
+    pub previous: Option<PathBuf>,
+    /// Also writes what changed since the previous index to this file, as JSON
+    #[arg(long, value_name = "FILE", requires = "previous")]
+    pub changes: Option<PathBuf>
+}
+
+pub fn run(_global: &GlobalOpts, args: &IndexArgs) -> ExitCode {
+    let index = match &args.previous {
+        Some(prev) => {
+            let previous: Index = match read_json(prev) {
+                Ok(previous) => previous,
+                Err(e) => return fail(prev, e)
+            };
+            let (index,changes) = match update_index(&args.dir, &previous) {
+                Ok(updated) => updated,
+                Err(e) => return fail(&args.dir, e)
+            };
+            for name in &changes.added {
+                eprintln!("added {}", name);
+            }
+            for name in &changes.removed {
+                eprintln!("removed {}", name);
+            }
+            for name in &changes.modified {
+                eprintln!("modified {}", name);
+            }
+            for name in &changes.failed {
+                eprintln!("failed {}", name);
+            }
+            if let Some(path) = &args.changes {
+                if let Err(e) = output(Some(path)).and_then(|mut out|write_json(&mut out, &changes)) {
+                    return fail(path, e);
+                }
+            }
+            index
+        }
+        None => match build_index(&args.dir) {
+            Ok(index) => index,
+            Err(e) => return fail(&args.dir, e)
+        }
+    };
+    match output(args.output.as_deref()).and_then(|mut out|write_json(&mut out, &index)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => fail(args.output.as_deref().unwrap_or(Path::new("-")), e)
+    }
+}
@@ -0,0 +1,57 @@
+use std::fs::read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::infer::infer_types;
+use mathparse::parse::{file_with_options,layout,ParseOptions};
+use mathparse::parse::generic::file_values;
+
+use super::{fail,segment_number,GlobalOpts};
+
+#[derive(Debug,Args)]
+pub struct InferTypesArgs {
+    /// Input .vo file
+    pub input: PathBuf,
+    /// Segment to look at, by number or by name (e.g. library)
+    #[arg(default_value = "0")]
+    pub segment: String
+}
+
+fn capitalized(name: &str) -> String {
+    let mut chars = name.chars();
+    chars.next().map(|c|c.to_ascii_uppercase().to_string() + chars.as_str()).unwrap_or_default()
+}
+
+pub fn run(_global: &GlobalOpts, args: &InferTypesArgs) -> ExitCode {
+    let contents = match read(&args.input) {
+        Ok(contents) => contents,
+        Err(e) => return fail(&args.input, e)
+    };
+    let options = ParseOptions{allow_trailing: true, ..ParseOptions::default()};
+    let magic = file_with_options(&options, &contents).ok().map(|(_,vo)|vo.magic).unwrap_or(0);
+    let names = layout(magic).map(|l|l.segments).unwrap_or(&[]);
+    let n = match segment_number(magic, &args.segment) {
+        Some(n) => n,
+        None => {
+            println!("Error no segment called {}", args.segment);
+            return ExitCode::FAILURE;
+        }
+    };
+    let name = names.get(n).map(|s|capitalized(s)).unwrap_or_else(||format!("Segment{}", n));
+    match file_values(&contents).into_iter().nth(n) {
+        Some(Ok(value)) => {
+            println!("{}", infer_types(&value, &name));
+            ExitCode::SUCCESS
+        }
+        Some(Err(e)) => {
+            println!("Error segment {}: {:?}", n, e.stuff.first().map(|(_,msg)|msg));
+            ExitCode::FAILURE
+        }
+        None => {
+            println!("Error no segment {}", n);
+            ExitCode::FAILURE
+        }
+    }
+}
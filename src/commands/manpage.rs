@@ -0,0 +1,14 @@
+use std::io::stdout;
+use std::process::ExitCode;
+
+use clap::CommandFactory;
+use clap_mangen::Man;
+
+use crate::Cli;
+
+use super::GlobalOpts;
+
+pub fn run(_global: &GlobalOpts) -> ExitCode {
+    Man::new(Cli::command()).render(&mut stdout()).unwrap();
+    ExitCode::SUCCESS
+}
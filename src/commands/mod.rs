@@ -0,0 +1,118 @@
+// One module per subcommand. Each has an Args struct for its own options and a run function taking
+// the global options as well, returning the process exit code.
+
+pub mod check;
+pub mod completions;
+pub mod corpus;
+pub mod diff;
+pub mod dump;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+pub mod graph;
+pub mod index;
+pub mod infer_types;
+pub mod manpage;
+pub mod schema;
+pub mod selftest;
+pub mod snapshot;
+pub mod stale;
+pub mod stats;
+pub mod triage;
+pub mod which;
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self,stdout,BufReader,Write};
+use std::path::{Path,PathBuf};
+use std::process::ExitCode;
+
+use clap::{ArgAction,Args,ValueEnum};
+
+use mathparse::meta::Report;
+use mathparse::parse::{layout,ParseOptions};
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,ValueEnum)]
+pub enum OutputFormat {
+    Human,
+    Json
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,ValueEnum)]
+pub enum LogFormat {
+    Human,
+    Json
+}
+
+#[derive(Debug,Args)]
+pub struct GlobalOpts {
+    /// Disables output messages
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// Increases message verbosity
+    #[arg(short = 'v', long = "verbose", action = ArgAction::Count, global = true)]
+    pub verbosity: u8,
+    /// Output format for reports
+    #[arg(long, value_enum, default_value_t = OutputFormat::Human, global = true)]
+    pub format: OutputFormat,
+    /// Writes log messages to a file instead of stderr
+    #[arg(long, value_name = "FILE", global = true)]
+    pub log_file: Option<PathBuf>,
+    /// Format of log messages: human-readable lines, or one JSON object per line
+    #[arg(long, value_enum, default_value_t = LogFormat::Human, global = true)]
+    pub log_format: LogFormat
+}
+
+impl GlobalOpts {
+    pub fn json(&self) -> bool {
+        self.format == OutputFormat::Json
+    }
+}
+
+// Reports a file or directory that couldn't be used; returns the exit code to give up with
+pub fn fail(path: &Path, e: impl Display) -> ExitCode {
+    println!("Error {}: {}", path.display(), e);
+    ExitCode::FAILURE
+}
+
+// The given file, or stdout
+pub fn output(file: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    Ok(match file {
+        Some(name) => Box::new(File::create(name)?),
+        None => Box::new(stdout())
+    })
+}
+
+// A JSON file, such as an index or corpus results from an earlier run
+pub fn read_json<T:serde::de::DeserializeOwned>(path: &Path) -> Result<T,String> {
+    let file = File::open(path).map_err(|e|e.to_string())?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|e|e.to_string())
+}
+
+pub fn write_json<T:serde::Serialize>(out: &mut dyn Write, value: &T) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, value)?;
+    writeln!(out)?;
+    out.flush()
+}
+
+// A segment given by number or by name (e.g. library), in a file with this magic number
+pub fn segment_number(magic: i32, segment: &str) -> Option<usize> {
+    segment.parse().ok().or_else(||layout(magic)?.segments.iter().position(|s|*s == segment))
+}
+
+pub fn print_json<T:serde::Serialize>(value: &T) -> io::Result<()> {
+    write_json(&mut stdout(), value)
+}
+
+// A --format json report, with a meta block for what produced it, parsing with default options
+pub fn print_report<T:serde::Serialize>(report: &T) -> io::Result<()> {
+    print_json(&Report::new(&ParseOptions::default(), report))
+}
+
+// Reports that stdout couldn't be written; returns the exit code to give up with. A closed pipe
+// (e.g. into head) isn't an error, since whatever reads the output has stopped wanting it.
+pub fn stdout_failed(e: io::Error) -> ExitCode {
+    if e.kind() == io::ErrorKind::BrokenPipe {
+        return ExitCode::SUCCESS;
+    }
+    fail(Path::new("writing to stdout"), e)
+}
@@ -0,0 +1,80 @@
+use std::process::ExitCode;
+
+use clap::{Args,ValueEnum};
+use schemars::{schema_for,Schema};
+
+use mathparse::corpus::{Corpus,CorpusComparison};
+use mathparse::diff::LibraryDiff;
+use mathparse::graph::GraphMetrics;
+use mathparse::index::{Index,IndexChanges,IndexEntry};
+use mathparse::meta::Report;
+use mathparse::stale::StaleReport;
+use mathparse::stats::{SegmentStats,StringReport};
+use mathparse::triage::Triage;
+
+use super::{GlobalOpts,print_json,stdout_failed};
+use super::which::Location;
+
+// Each subcommand output that --format json can produce. All but the index, its changes and the
+// corpus record are wrapped in a Report, which adds a meta block; the index has its own.
+#[derive(Debug,Clone,Copy,ValueEnum)]
+pub enum SchemaName {
+    /// 'mathparse index'
+    Index,
+    /// The file written by 'mathparse index --changes'
+    IndexChanges,
+    /// 'mathparse which'
+    Which,
+    /// 'mathparse stale'
+    Stale,
+    /// 'mathparse corpus'
+    Corpus,
+    /// The file written by 'mathparse corpus --record'
+    CorpusRecord,
+    /// 'mathparse corpus --expect'
+    CorpusComparison,
+    /// 'mathparse graph'
+    Graph,
+    /// 'mathparse diff'
+    Diff,
+    /// 'mathparse stats'
+    Stats,
+    /// 'mathparse stats --strings'
+    StatsStrings,
+    /// 'mathparse triage'
+    Triage,
+    /// 'mathparse load-snapshot'
+    LoadSnapshot
+}
+
+#[derive(Debug,Args)]
+pub struct SchemaArgs {
+    /// Which output to describe
+    #[arg(value_enum)]
+    pub name: SchemaName
+}
+
+pub fn schema(name: SchemaName) -> Schema {
+    match name {
+        SchemaName::Index => schema_for!(Index),
+        SchemaName::IndexChanges => schema_for!(IndexChanges),
+        SchemaName::Which => schema_for!(Report<Location>),
+        SchemaName::Stale => schema_for!(Report<Vec<StaleReport>>),
+        SchemaName::Corpus => schema_for!(Report<Corpus>),
+        SchemaName::CorpusRecord => schema_for!(Corpus),
+        SchemaName::CorpusComparison => schema_for!(Report<CorpusComparison>),
+        SchemaName::Graph => schema_for!(Report<GraphMetrics>),
+        SchemaName::Diff => schema_for!(Report<Vec<LibraryDiff>>),
+        SchemaName::Stats => schema_for!(Report<Vec<SegmentStats>>),
+        SchemaName::StatsStrings => schema_for!(Report<Vec<StringReport>>),
+        SchemaName::Triage => schema_for!(Report<Triage>),
+        SchemaName::LoadSnapshot => schema_for!(Report<IndexEntry>)
+    }
+}
+
+pub fn run(_global: &GlobalOpts, args: &SchemaArgs) -> ExitCode {
+    match print_json(&schema(args.name)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => stdout_failed(e)
+    }
+}
@@ -0,0 +1,18 @@
+use std::process::ExitCode;
+
+use mathparse::selftest::selftest;
+
+use super::GlobalOpts;
+
+pub fn run(_global: &GlobalOpts) -> ExitCode {
+    match selftest() {
+        Ok(()) => {
+            println!("selftest passed");
+            ExitCode::SUCCESS
+        }
+        Err(msg) => {
+            println!("selftest failed: {}", msg);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::fs::{read,write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::index::IndexEntry;
+use mathparse::parse::file;
+use mathparse::snapshot::{load_snapshot,save_snapshot};
+
+use super::{fail,GlobalOpts,print_report,stdout_failed};
+
+#[derive(Debug,Args)]
+pub struct SnapshotArgs {
+    /// Input .vo file to parse
+    pub input: PathBuf,
+    /// Where to write the snapshot
+    #[arg(short, long)]
+    pub output: PathBuf
+}
+
+#[derive(Debug,Args)]
+pub struct LoadSnapshotArgs {
+    /// Snapshot written by 'mathparse snapshot'
+    pub input: PathBuf
+}
+
+pub fn run(_global: &GlobalOpts, args: &SnapshotArgs) -> ExitCode {
+    let contents = match read(&args.input) {
+        Ok(contents) => contents,
+        Err(e) => return fail(&args.input, e)
+    };
+    match file(&contents) {
+        Ok((_,vo)) => {
+            match write(&args.output, save_snapshot(&vo)) {
+                Ok(()) => ExitCode::SUCCESS,
+                Err(e) => fail(&args.output, e)
+            }
+        }
+        Err(e) => {
+            println!("{}: {:?}", args.input.display(), e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn run_load(global: &GlobalOpts, args: &LoadSnapshotArgs) -> ExitCode {
+    let contents = match read(&args.input) {
+        Ok(contents) => contents,
+        Err(e) => return fail(&args.input, e)
+    };
+    let vo = match load_snapshot(&contents) {
+        Ok(vo) => vo,
+        Err(e) => {
+            println!("{}: {}", args.input.display(), e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if global.json() {
+        if let Err(e) = print_report(&IndexEntry::new(args.input.to_string_lossy().into_owned(), &vo)) {
+            return stdout_failed(e);
+        }
+    } else {
+        println!("{}: {} imports, {} deps, {} segments",
+            vo.summary.name(),
+            vo.summary.imports().len(),
+            vo.summary.deps().len(),
+            vo.segments.len());
+    }
+    ExitCode::SUCCESS
+}
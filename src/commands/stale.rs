@@ -0,0 +1,34 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::index::build_index;
+use mathparse::stale::check_stale;
+
+use super::{fail,GlobalOpts,print_report,stdout_failed};
+
+#[derive(Debug,Args)]
+pub struct StaleArgs {
+    /// Root directory of the development
+    pub dir: PathBuf
+}
+
+pub fn run(global: &GlobalOpts, args: &StaleArgs) -> ExitCode {
+    let reports = match build_index(&args.dir).and_then(|index|check_stale(&args.dir, &index)) {
+        Ok(reports) => reports,
+        Err(e) => return fail(&args.dir, e)
+    };
+    if global.json() {
+        if let Err(e) = print_report(&reports) {
+            return stdout_failed(e);
+        }
+    } else {
+        for report in &reports {
+            for problem in &report.problems {
+                println!("{}: {}", report.file, problem);
+            }
+        }
+    }
+    if reports.is_empty() { ExitCode::SUCCESS } else { ExitCode::FAILURE }
+}
@@ -0,0 +1,73 @@
+use std::fs::read;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::stats::{file_stats,file_string_reports};
+
+use super::{fail,GlobalOpts,print_report,stdout_failed};
+
+#[derive(Debug,Args)]
+pub struct StatsArgs {
+    /// Input .vo file
+    pub input: PathBuf,
+    /// Report wasted bytes in string encodings and strings that could be shared instead
+    #[arg(long)]
+    pub strings: bool
+}
+
+fn histogram(h: &[usize]) -> String {
+    h.iter().enumerate().filter(|(_,&n)|n > 0)
+        .map(|(k,n)|format!("<{}:{}", 1usize << k, n))
+        .collect::<Vec<_>>().join(" ")
+}
+
+fn run_strings(global: &GlobalOpts, contents: &[u8]) -> ExitCode {
+    let reports = file_string_reports(contents);
+    if global.json() {
+        if let Err(e) = print_report(&reports) {
+            return stdout_failed(e);
+        }
+    } else {
+        for (n,r) in reports.iter().enumerate() {
+            println!("segment {}: {} strings in {} bytes, {} bytes wasted on length encodings", n, r.strings, r.bytes, r.wasted_encoding);
+            for d in &r.duplicates {
+                println!("  {:?}: {} copies, sharing would save {} bytes", d.string, d.copies, d.savings);
+            }
+            if let Some(e) = &r.error {
+                println!("  stopped early: {}", e);
+            }
+        }
+    }
+    if reports.iter().any(|r|r.error.is_some()) { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
+
+pub fn run(global: &GlobalOpts, args: &StatsArgs) -> ExitCode {
+    let contents = match read(&args.input) {
+        Ok(contents) => contents,
+        Err(e) => return fail(&args.input, e)
+    };
+    if args.strings {
+        return run_strings(global, &contents);
+    }
+    let stats = file_stats(&contents);
+    if global.json() {
+        if let Err(e) = print_report(&stats) {
+            return stdout_failed(e);
+        }
+    } else {
+        for (n,s) in stats.iter().enumerate() {
+            println!("segment {}: {} objects, longest block {}", n, s.objects, s.max_block_len);
+            for (code,count) in &s.code_counts {
+                println!("  {:<22} {}", code, count);
+            }
+            println!("  pointer distances: {}", histogram(&s.pointer_distances));
+            println!("  string lengths:    {}", histogram(&s.string_lengths));
+            if let Some(e) = &s.error {
+                println!("  stopped early: {}", e);
+            }
+        }
+    }
+    if stats.iter().any(|s|s.error.is_some()) { ExitCode::FAILURE } else { ExitCode::SUCCESS }
+}
@@ -0,0 +1,44 @@
+use std::fs::read;
+use std::path::{Path,PathBuf};
+use std::process::ExitCode;
+
+use clap::Args;
+
+use mathparse::meta::Report;
+use mathparse::parse::ParseOptions;
+use mathparse::triage::triage;
+
+use super::{fail,output,write_json,GlobalOpts};
+
+#[derive(Debug,Args)]
+pub struct TriageArgs {
+    /// Input .vo file
+    pub input: PathBuf,
+    /// Output file for the bundle (default: stdout)
+    #[arg(short, long)]
+    pub output: Option<PathBuf>,
+    /// The most problems to report per segment from the lenient scan
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub max_errors: usize
+}
+
+pub fn run(_global: &GlobalOpts, args: &TriageArgs) -> ExitCode {
+    let contents = match read(&args.input) {
+        Ok(contents) => contents,
+        Err(e) => return fail(&args.input, e)
+    };
+    let options = ParseOptions::default();
+    match triage(&contents, &options, args.max_errors) {
+        Some(bundle) => {
+            let written = output(args.output.as_deref()).and_then(|mut out|write_json(&mut out, &Report::new(&options, bundle)));
+            match written {
+                Ok(()) => ExitCode::FAILURE,
+                Err(e) => fail(args.output.as_deref().unwrap_or(Path::new("-")), e)
+            }
+        }
+        None => {
+            println!("{} parses without errors, nothing to triage", args.input.display());
+            ExitCode::SUCCESS
+        }
+    }
+}
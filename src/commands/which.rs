@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Args;
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use mathparse::index::Index;
+
+use super::{fail,read_json,GlobalOpts,print_report,stdout_failed};
+
+#[derive(Debug,Args)]
+pub struct WhichArgs {
+    /// Qualified name, e.g. Coq.Init.Nat.add
+    pub name: String,
+    /// index.json to search, as written by 'mathparse index'
+    #[arg(short, long)]
+    pub index: PathBuf
+}
+
+#[derive(Serialize,JsonSchema)]
+pub struct Location<'a> {
+    name: &'a str,
+    file: &'a str,
+    library: &'a str,
+    module: &'a str,
+    digest: Option<&'a str>
+}
+
+pub fn run(global: &GlobalOpts, args: &WhichArgs) -> ExitCode {
+    let name = args.name.as_str();
+    let index: Index = match read_json(&args.index) {
+        Ok(index) => index,
+        Err(e) => return fail(&args.index, e)
+    };
+    let entry = match index.resolve(name) {
+        Some(entry) => entry,
+        None => {
+            println!("{}: no library in the index could define this name", name);
+            return ExitCode::FAILURE;
+        }
+    };
+    let location = Location {
+        name,
+        file: &entry.file,
+        library: &entry.name,
+        module: &name[..name.rfind('.').unwrap()],
+        digest: entry.digest.as_deref()
+    };
+    if global.json() {
+        if let Err(e) = print_report(&location) {
+            return stdout_failed(e);
+        }
+    } else {
+        println!("{}", location.name);
+        println!("  file:    {}", location.file);
+        println!("  library: {}", location.library);
+        println!("  module:  {}", location.module);
+        println!("  digest:  {}", location.digest.unwrap_or("-"));
+    }
+    ExitCode::SUCCESS
+}
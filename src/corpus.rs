@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::fs::read;
+use std::io;
+use std::path::Path;
+
+use schemars::JsonSchema;
+use serde::{Deserialize,Serialize};
+
+use crate::index::find_vo_files;
+use crate::loadpath::relative_name;
+use crate::parse::file;
+
+// What parsing one file produced; error text is kept for information but not compared
+#[derive(Debug,Clone,Serialize,Deserialize,JsonSchema)]
+pub struct CorpusResult {
+    pub file: String,
+    pub ok: bool,
+    pub name: Option<String>,
+    pub digest: Option<String>,
+    pub segments: usize,
+    #[serde(default)]
+    pub bad_digests: Vec<usize>,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub error_offset: Option<usize>
+}
+
+impl CorpusResult {
+    // Differences in the compared fields, as human-readable messages
+    fn differences(&self, expected: &CorpusResult) -> Vec<String> {
+        let mut result = vec![];
+        match (expected.ok, self.ok) {
+            (true, false) => result.push(format!("{}: expected to parse, now fails: {}", self.file, self.error.as_deref().unwrap_or("?"))),
+            (false, true) => result.push(format!("{}: expected to fail, now parses", self.file)),
+            _ => {}
+        }
+        if self.ok && expected.ok {
+            if self.name != expected.name {
+                result.push(format!("{}: name {:?}, expected {:?}", self.file, self.name, expected.name));
+            }
+            if self.digest != expected.digest {
+                result.push(format!("{}: digest {:?}, expected {:?}", self.file, self.digest, expected.digest));
+            }
+            if self.segments != expected.segments {
+                result.push(format!("{}: {} segments, expected {}", self.file, self.segments, expected.segments));
+            }
+            if self.bad_digests != expected.bad_digests {
+                result.push(format!("{}: digest mismatch in segments {:?}, expected {:?}", self.file, self.bad_digests, expected.bad_digests));
+            }
+        }
+        result
+    }
+}
+
+#[derive(Debug,Clone,Default,Serialize,Deserialize,JsonSchema)]
+pub struct Corpus {
+    pub results: Vec<CorpusResult>
+}
+
+pub fn corpus_result(dir: &Path, path: &Path) -> io::Result<CorpusResult> {
+    let contents = read(path)?;
+    let rel = relative_name(dir, path);
+    Ok(match file(&contents) {
+        Ok((_,vo)) => CorpusResult {
+            file: rel,
+            ok: true,
+            name: Some(vo.summary.name().to_string()),
+            digest: vo.library_digest().map(|d|d.to_hex()),
+            segments: vo.segments.len(),
+            bad_digests: vo.bad_digests(&contents),
+            error: None,
+            error_offset: None
+        },
+        Err(e) => {
+            let (error,error_offset) = match e {
+                nom::Err::Error(e) | nom::Err::Failure(e) => match e.stuff.first() {
+                    Some((remaining,msg)) => (msg.clone(), Some(contents.len() - remaining)),
+                    None => ("unknown error".to_string(), None)
+                },
+                nom::Err::Incomplete(needed) => (format!("Incomplete({:?})", needed), None)
+            };
+            CorpusResult{file:rel, ok:false, name:None, digest:None, segments:0, bad_digests:vec![], error:Some(error), error_offset}
+        }
+    })
+}
+
+pub fn run_corpus(dir: &Path) -> io::Result<Corpus> {
+    let mut results = vec![];
+    for path in find_vo_files(dir)? {
+        results.push(corpus_result(dir, &path)?);
+    }
+    Ok(Corpus{results})
+}
+
+#[derive(Debug,Clone,Default,Serialize,JsonSchema)]
+pub struct CorpusComparison {
+    pub total: usize,
+    pub as_expected: usize,
+    pub differences: Vec<String>
+}
+
+// Files that failed with the same (innermost) error message
+#[derive(Debug,Clone)]
+pub struct ErrorGroup {
+    pub message: String,
+    pub files: Vec<(String,Option<usize>)>
+}
+
+impl Corpus {
+    // Largest groups first, ties broken by message; files within a group keep corpus order
+    pub fn error_groups(&self) -> Vec<ErrorGroup> {
+        let mut groups: BTreeMap<&str,Vec<(String,Option<usize>)>> = BTreeMap::new();
+        for result in &self.results {
+            if let Some(error) = &result.error {
+                groups.entry(error.as_str()).or_default().push((result.file.clone(), result.error_offset));
+            }
+        }
+        let mut result: Vec<ErrorGroup> = groups.into_iter().map(|(message,files)|ErrorGroup{message:message.to_string(), files}).collect();
+        result.sort_by(|a,b|b.files.len().cmp(&a.files.len()).then_with(||a.message.cmp(&b.message)));
+        result
+    }
+
+    pub fn compare(&self, expected: &Corpus) -> CorpusComparison {
+        let mut expected_by_file: BTreeMap<&str,&CorpusResult> = expected.results.iter().map(|r|(r.file.as_str(),r)).collect();
+        let mut comparison = CorpusComparison{total:self.results.len(), ..Default::default()};
+        for result in &self.results {
+            match expected_by_file.remove(result.file.as_str()) {
+                Some(exp) => {
+                    let diffs = result.differences(exp);
+                    if diffs.is_empty() {
+                        comparison.as_expected += 1;
+                    }
+                    comparison.differences.extend(diffs);
+                }
+                None => comparison.differences.push(format!("{}: not in expectations", result.file))
+            }
+        }
+        for file in expected_by_file.keys() {
+            comparison.differences.push(format!("{}: expected but not found", file));
+        }
+        comparison
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(file: &str, digest: &str) -> CorpusResult {
+        CorpusResult{file:file.to_string(), ok:true, name:Some(file.to_uppercase()), digest:Some(digest.to_string()), segments:5, bad_digests:vec![], error:None, error_offset:None}
+    }
+
+    fn failed(file: &str, error: &str) -> CorpusResult {
+        CorpusResult{file:file.to_string(), ok:false, name:None, digest:None, segments:0, bad_digests:vec![], error:Some(error.to_string()), error_offset:Some(0x10)}
+    }
+
+    #[test]
+    fn comparison() {
+        let expected = Corpus{results: vec![parsed("a", "aa"), parsed("b", "bb"), parsed("c", "cc"), failed("d", "old error"), parsed("gone", "gg")]};
+        let mut b = parsed("b", "b2");
+        b.segments = 4;
+        b.bad_digests = vec![2];
+        // d fails with a different message, which isn't compared
+        let actual = Corpus{results: vec![parsed("a", "aa"), b, failed("c", "Unhandled code 0x18"), failed("d", "new error"), parsed("new", "nn")]};
+        let comparison = actual.compare(&expected);
+        assert_eq!((comparison.total, comparison.as_expected), (5, 2));
+        assert_eq!(comparison.differences, vec![
+            "b: digest Some(\"b2\"), expected Some(\"bb\")",
+            "b: 4 segments, expected 5",
+            "b: digest mismatch in segments [2], expected []",
+            "c: expected to parse, now fails: Unhandled code 0x18",
+            "new: not in expectations",
+            "gone: expected but not found"
+        ]);
+        let comparison = expected.compare(&Corpus{results: vec![failed("a", "?")]});
+        assert_eq!(&comparison.differences[..1], &["a: expected to fail, now parses"]);
+    }
+
+    #[test]
+    fn groups_by_size_then_message() {
+        let corpus = Corpus{results: vec![failed("a", "Zeta"), parsed("b", "bb"), failed("c", "Beta"), failed("d", "Zeta"), failed("e", "Alpha"), failed("f", "Beta")]};
+        let groups: Vec<(String,Vec<String>)> = corpus.error_groups().into_iter()
+            .map(|g|(g.message, g.files.into_iter().map(|(file,_)|file).collect()))
+            .collect();
+        assert_eq!(groups, vec![
+            ("Beta".to_string(), vec!["c".to_string(), "f".to_string()]),
+            ("Zeta".to_string(), vec!["a".to_string(), "d".to_string()]),
+            ("Alpha".to_string(), vec!["e".to_string()])
+        ]);
+    }
+}
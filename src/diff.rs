@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::fs::read;
+use std::io;
+use std::path::{Path,PathBuf};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::index::find_vo_files;
+use crate::parse::{file,VoFile};
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,JsonSchema)]
+pub enum Change {
+    Added,
+    Removed,
+    Unchanged,
+    // Only the opaque proofs changed, so dependents needn't be rebuilt
+    OpaqueOnly,
+    // The library digest is the same, so dependents needn't be rebuilt, but the summary, universes
+    // or tasks changed (perhaps with the opaque proofs too)
+    Metadata,
+    // The library segment changed. That's either the interface or a transparent body; telling
+    // which needs the library segment parsed.
+    Digest
+}
+
+#[derive(Debug,Clone,Serialize,JsonSchema)]
+pub struct LibraryDiff {
+    pub name: String,
+    pub change: Change,
+    // Names of the segments whose recorded digests differ
+    pub segments: Vec<String>
+}
+
+fn libraries(dir: &Path) -> io::Result<BTreeMap<String,VoFile>> {
+    let mut result = BTreeMap::new();
+    let mut paths: BTreeMap<String,PathBuf> = BTreeMap::new();
+    for path in find_vo_files(dir)? {
+        let contents = read(&path)?;
+        match file(&contents) {
+            Ok((_,vo)) => {
+                let name = vo.summary.name().to_string();
+                match paths.get(&name) {
+                    Some(first) => warn!("Skipping {}: {} is also library {}", path.display(), first.display(), name),
+                    None => {
+                        paths.insert(name.clone(), path);
+                        result.insert(name, vo);
+                    }
+                }
+            }
+            Err(e) => warn!("Skipping {}: {:?}", path.display(), e)
+        }
+    }
+    Ok(result)
+}
+
+fn compare(name: &str, old: &VoFile, new: &VoFile) -> LibraryDiff {
+    let count = old.segments.len().max(new.segments.len());
+    let changed: Vec<usize> = (0..count).filter(|&n| {
+        old.segments.get(n).map(|s|&s.digest) != new.segments.get(n).map(|s|&s.digest)
+    }).collect();
+    let change = if changed.is_empty() {
+        Change::Unchanged
+    } else if old.library_digest() != new.library_digest() {
+        Change::Digest
+    } else if changed.iter().all(|&n|new.segment_name(n) == Some("opaques")) {
+        Change::OpaqueOnly
+    } else {
+        Change::Metadata
+    };
+    let segments = changed.iter().map(|&n|new.segment_name(n).unwrap_or("?").to_string()).collect();
+    LibraryDiff{name:name.to_string(), change, segments}
+}
+
+// Compares two builds of a development, pairing .vo files by logical name. Files that don't parse are skipped.
+pub fn diff_dirs(old_dir: &Path, new_dir: &Path) -> io::Result<Vec<LibraryDiff>> {
+    let old = libraries(old_dir)?;
+    let new = libraries(new_dir)?;
+    let mut result = vec![];
+    for (name,old_vo) in &old {
+        match new.get(name) {
+            Some(new_vo) => result.push(compare(name, old_vo, new_vo)),
+            None => result.push(LibraryDiff{name:name.clone(), change:Change::Removed, segments:vec![]})
+        }
+    }
+    for name in new.keys().filter(|n|!old.contains_key(*n)) {
+        result.push(LibraryDiff{name:name.clone(), change:Change::Added, segments:vec![]});
+    }
+    result.sort_by(|a,b|a.name.cmp(&b.name));
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn changed_segments() {
+        let sample = include_bytes!("selftest.vo");
+        let old = file(sample).unwrap().1;
+        assert_eq!(compare("A", &old, &old).change, Change::Unchanged);
+        // the recorded digests of the opaques (after 0x12c) and library (after 0xb1) segments
+        let mut contents = sample.to_vec();
+        contents[0x12c] ^= 1;
+        let new = file(&contents).unwrap().1;
+        let d = compare("A", &old, &new);
+        assert_eq!((d.change, d.segments), (Change::OpaqueOnly, vec!["opaques".to_string()]));
+        // and the summary's, which isn't an opaque proof
+        let mut summary_too = contents.clone();
+        summary_too[old.segments[0].stop] ^= 1;
+        let d = compare("A", &old, &file(&summary_too).unwrap().1);
+        assert_eq!((d.change, d.segments), (Change::Metadata, vec!["summary".to_string(), "opaques".to_string()]));
+        contents[0xb1] ^= 1;
+        let new = file(&contents).unwrap().1;
+        let d = compare("A", &old, &new);
+        assert_eq!((d.change, d.segments), (Change::Digest, vec!["library".to_string(), "opaques".to_string()]));
+    }
+
+    #[test]
+    fn duplicate_names() {
+        // two files claiming the same library: the first in path order is compared, the other skipped
+        let dir = std::env::temp_dir().join(format!("mathparse-diff-duplicates-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let (old_dir,new_dir) = (dir.join("old"), dir.join("new"));
+        std::fs::create_dir_all(&old_dir).unwrap();
+        std::fs::create_dir_all(&new_dir).unwrap();
+        let sample = include_bytes!("selftest.vo");
+        let mut changed = sample.to_vec();
+        changed[0x12c] ^= 1;
+        std::fs::write(old_dir.join("A.vo"), sample).unwrap();
+        std::fs::write(new_dir.join("A.vo"), sample).unwrap();
+        std::fs::write(new_dir.join("B.vo"), &changed).unwrap();
+        let diffs = diff_dirs(&old_dir, &new_dir).unwrap();
+        assert_eq!(diffs.iter().map(|d|(d.name.as_str(), d.change)).collect::<Vec<_>>(), vec![("Mathparse.Selftest", Change::Unchanged)]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
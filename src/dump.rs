@@ -0,0 +1,260 @@
+// Writes marshalled data as JSON while decoding it, for segments too large to hold as a
+// parse::generic::Value tree. It reads the same objects as generic::value, in the same order, but
+// keeps only the blocks still open, so memory doesn't grow with the output. Shared objects carry an
+// "id", their place in the shared-object table, and pointers are written as {"ref": id}, so
+// sharing and cycles come out without copying anything.
+
+use std::io::{self,Write};
+
+use crate::parse::{ObjectIter,Repr,E};
+
+// A block whose fields are being written: how many it has, and how many are still to come
+struct Open {
+    len: usize,
+    remaining: usize
+}
+
+fn invalid(data: &[u8], offset: usize, msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{} at {:#x} in the segment's data", msg, offset.min(data.len())))
+}
+
+fn decode_error(data: &[u8], e: E) -> io::Error {
+    match e.stuff.first() {
+        Some((remaining,msg)) => invalid(data, data.len() - remaining, msg),
+        None => invalid(data, data.len(), "Undecodable object")
+    }
+}
+
+fn write_hex<W:Write>(out: &mut W, bytes: &[u8]) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    for b in bytes {
+        write!(out, "{:02x}", b)?;
+    }
+    out.write_all(b"\"")
+}
+
+// A shared object other than a block, which takes the given place in the table
+fn write_leaf<W:Write>(out: &mut W, id: usize, repr: &Repr) -> io::Result<()> {
+    write!(out, "{{\"id\":{},", id)?;
+    match repr {
+        Repr::RString(s) => match std::str::from_utf8(s) {
+            Ok(s) => {
+                out.write_all(b"\"string\":")?;
+                serde_json::to_writer(&mut *out, s)?;
+            }
+            Err(_) => {
+                out.write_all(b"\"bytes\":")?;
+                write_hex(out, s)?;
+            }
+        },
+        // JSON has no NaN or infinities, so those are written as strings
+        Repr::RDouble(x) if x.is_finite() => {
+            out.write_all(b"\"double\":")?;
+            serde_json::to_writer(&mut *out, x)?;
+        }
+        Repr::RDouble(x) => write!(out, "\"double\":\"{}\"", x)?,
+        Repr::RInt63(n) => write!(out, "\"uint63\":{}", n)?,
+        Repr::RInt32(n) => write!(out, "\"int32\":{}", n)?,
+        Repr::RInt64(n) => write!(out, "\"int64\":{}", n)?,
+        Repr::RNativeInt(n) => write!(out, "\"nativeint\":{}", n)?,
+        // as a decimal string, since JSON readers often can't hold big numbers
+        Repr::RBigInt(n) => write!(out, "\"bigint\":\"{}\"", n)?,
+        Repr::RCustom(ident,payload) => {
+            out.write_all(b"\"custom\":")?;
+            serde_json::to_writer(&mut *out, ident)?;
+            out.write_all(b",\"payload\":")?;
+            write_hex(out, payload)?;
+        }
+        Repr::RInt(_) | Repr::RBlock(..) | Repr::RPointer(_) | Repr::RCodePointer{..} => unreachable!()
+    }
+    out.write_all(b"}")
+}
+
+// How much of a value to write. Whatever is left out is replaced by a string saying how much was
+// skipped, so the output stays valid JSON.
+#[derive(Debug,Clone,Copy,Default,PartialEq,Eq)]
+pub struct DumpLimits {
+    // Blocks this deep are summarized instead of written; the whole value is at depth 0
+    pub max_depth: Option<usize>,
+    // After this many values (or summaries of them) are written, the remaining fields of each open block are summarized
+    pub max_nodes: Option<usize>
+}
+
+// Values being read but not written, and what to say about them once they end
+struct Skip {
+    pending: usize,
+    nodes: usize,
+    block: Option<(u8,usize)>,
+    fields: usize
+}
+
+// Whether the object takes a place in the shared-object table
+fn is_shared(repr: &Repr) -> bool {
+    !matches!(repr, Repr::RInt(_) | Repr::RBlock(_,0) | Repr::RPointer(_) | Repr::RCodePointer{..})
+}
+
+// Writes the value encoded by some marshalled data (without its header) as one line of JSON. The
+// data must hold exactly one value. On an error, what has been written so far is cut off mid-value.
+pub fn dump_json<W:Write>(data: &[u8], limits: &DumpLimits, out: &mut W) -> io::Result<()> {
+    let mut stack: Vec<Open> = vec![];
+    let mut shared: usize = 0;
+    let mut written = 0;
+    let mut skip: Option<Skip> = None;
+    let mut done = false;
+    for object in ObjectIter::new(data) {
+        let (offset,repr) = object.map_err(|e|decode_error(data, e))?;
+        if done {
+            return Err(invalid(data, offset, "Data continues after the value ends"));
+        }
+        if let Repr::RPointer(n) = repr {
+            if n == 0 || n > shared {
+                return Err(invalid(data, offset, &format!("Shared pointer {} is out of range", n)));
+            }
+        }
+        if skip.is_none() {
+            if stack.last().is_some_and(|o|o.remaining < o.len) {
+                out.write_all(b",")?;
+            }
+            let over_nodes = limits.max_nodes.is_some_and(|max|written >= max);
+            let too_deep = matches!(repr, Repr::RBlock(_,len) if len > 0) && limits.max_depth.is_some_and(|max|stack.len() >= max);
+            if over_nodes || too_deep {
+                let fields = match stack.last() {
+                    Some(open) if over_nodes => open.remaining,
+                    _ => 1
+                };
+                let block = match repr {
+                    Repr::RBlock(tag,len) if !over_nodes => Some((tag,len)),
+                    _ => None
+                };
+                skip = Some(Skip{pending: fields, nodes: 0, block, fields});
+            }
+        }
+        let completed = if let Some(sk) = &mut skip {
+            sk.nodes += 1;
+            sk.pending -= 1;
+            if let Repr::RBlock(_,len) = repr {
+                sk.pending += len;
+            }
+            if is_shared(&repr) {
+                shared += 1;
+            }
+            if sk.pending > 0 {
+                continue;
+            }
+            match sk.block {
+                Some((tag,len)) => write!(out, "\"<block tag={} with {} fields, {} nodes>\"", tag, len, sk.nodes)?,
+                None if stack.is_empty() => write!(out, "\"<{} nodes>\"", sk.nodes)?,
+                None => write!(out, "\"<{} more fields, {} nodes>\"", sk.fields, sk.nodes)?
+            }
+            written += 1;
+            let fields = sk.fields;
+            skip = None;
+            fields
+        } else {
+            written += 1;
+            match repr {
+                Repr::RBlock(tag,0) => write!(out, "{{\"tag\":{},\"fields\":[]}}", tag)?,
+                Repr::RBlock(tag,len) => {
+                    write!(out, "{{\"id\":{},\"tag\":{},\"fields\":[", shared, tag)?;
+                    shared += 1;
+                    stack.push(Open{len, remaining: len});
+                    continue;
+                }
+                Repr::RInt(n) => write!(out, "{}", n)?,
+                Repr::RPointer(n) => write!(out, "{{\"ref\":{}}}", shared - n)?,
+                Repr::RCodePointer{offset,digest} => write!(out, "{{\"code_pointer\":{},\"digest\":\"{}\"}}", offset, digest.to_hex())?,
+                repr => {
+                    write_leaf(out, shared, &repr)?;
+                    shared += 1;
+                }
+            }
+            1
+        };
+        // count the values against their parent, closing every block that they complete
+        let mut values = completed;
+        loop {
+            match stack.last_mut() {
+                None => {
+                    done = true;
+                    break;
+                }
+                Some(open) => {
+                    open.remaining -= values;
+                    values = 1;
+                    if open.remaining > 0 {
+                        break;
+                    }
+                    stack.pop();
+                    out.write_all(b"]}")?;
+                }
+            }
+        }
+    }
+    if !done {
+        return Err(invalid(data, data.len(), "Data ends before the value does"));
+    }
+    writeln!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{block,double,int,list,str};
+
+    fn dump(data: &[u8]) -> io::Result<String> {
+        dump_limited(data, DumpLimits::default())
+    }
+
+    fn dump_limited(data: &[u8], limits: DumpLimits) -> io::Result<String> {
+        let mut out = vec![];
+        dump_json(data, &limits, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn values_and_sharing() {
+        let v = block(1).fields(vec![str("a\"b"), int(-3), double(0.5), block(0), list(vec![int(7)])]).shared(4);
+        assert_eq!(dump(&v.to_bytes()).unwrap(),
+            "{\"id\":0,\"tag\":1,\"fields\":[{\"id\":1,\"string\":\"a\\\"b\"},-3,{\"id\":2,\"double\":0.5},{\"tag\":0,\"fields\":[]},{\"id\":3,\"tag\":0,\"fields\":[7,0]},{\"ref\":0}]}\n");
+        // the output is JSON, and non-UTF-8 strings come out as hex
+        let json: serde_json::Value = serde_json::from_str(&dump(&[0xa0, 0x22, 0xff, b'x', 0x40]).unwrap()).unwrap();
+        assert_eq!(json["fields"][0]["bytes"], "ff78");
+    }
+
+    #[test]
+    fn deep_list() {
+        // [0; 0; ...; 0] with 100000 elements, which would overflow the stack if written recursively
+        let mut data = vec![];
+        for _ in 0..100_000 {
+            data.extend_from_slice(&[0xa0, 0x40]);
+        }
+        data.push(0x40);
+        let out = dump(&data).unwrap();
+        assert!(out.trim_end().ends_with(&"]}".repeat(100_000)));
+    }
+
+    #[test]
+    fn errors() {
+        assert_eq!(dump(&[0xa0, 0x41]).unwrap_err().to_string(), "Data ends before the value does at 0x2 in the segment's data");
+        assert_eq!(dump(&[0x41, 0x41]).unwrap_err().to_string(), "Data continues after the value ends at 0x1 in the segment's data");
+        assert_eq!(dump(&[0x90, 0x04, 0x02]).unwrap_err().to_string(), "Shared pointer 2 is out of range at 0x1 in the segment's data");
+    }
+
+    #[test]
+    fn limits() {
+        // (1, (2, 3), [4; 5]), with the list shared with a later field
+        let l = list(vec![int(4), int(5)]);
+        let v = block(0).fields(vec![int(1), block(0).fields(vec![int(2), int(3)]), l]).shared(2);
+        let data = v.to_bytes();
+        assert_eq!(dump_limited(&data, DumpLimits{max_depth: Some(1), max_nodes: None}).unwrap(),
+            "{\"id\":0,\"tag\":0,\"fields\":[1,\"<block tag=0 with 2 fields, 3 nodes>\",\"<block tag=0 with 2 fields, 5 nodes>\",{\"ref\":2}]}\n");
+        // ids keep counting through what is skipped
+        assert_eq!(dump_limited(&data, DumpLimits{max_depth: None, max_nodes: Some(4)}).unwrap(),
+            "{\"id\":0,\"tag\":0,\"fields\":[1,{\"id\":1,\"tag\":0,\"fields\":[2,\"<1 more fields, 1 nodes>\"]},\"<2 more fields, 6 nodes>\"]}\n");
+        assert_eq!(dump_limited(&data, DumpLimits{max_depth: Some(0), max_nodes: None}).unwrap(),
+            "\"<block tag=0 with 4 fields, 11 nodes>\"\n");
+        assert_eq!(dump_limited(&data, DumpLimits{max_depth: None, max_nodes: Some(0)}).unwrap(), "\"<11 nodes>\"\n");
+        // a limit that isn't reached changes nothing
+        assert_eq!(dump_limited(&data, DumpLimits{max_depth: Some(5), max_nodes: Some(100)}).unwrap(), dump(&data).unwrap());
+    }
+}
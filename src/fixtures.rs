@@ -0,0 +1,155 @@
+// Builds the fixture corpus in tests/fixtures: compiles each tiny .v source there with every Coq
+// installation found, and records what mathparse makes of the results, so a new Coq version is
+// covered by installing it and rerunning 'mathparse fixtures'. Needs the fixtures feature.
+
+use std::env;
+use std::ffi::OsStr;
+use std::fs::{copy,create_dir_all,read_dir,read_to_string,remove_dir_all,File};
+use std::io;
+use std::path::{Path,PathBuf};
+use std::process::Command;
+
+use crate::corpus::{Corpus,run_corpus};
+
+#[derive(Debug,Clone)]
+pub struct Coqc {
+    pub path: PathBuf,
+    // As printed by coqc --print-version, e.g. 8.18.0
+    pub version: String
+}
+
+// The first line of a source can limit it to newer versions: (* mathparse: since 8.13 *)
+#[derive(Debug,Clone)]
+pub struct Fixture {
+    pub path: PathBuf,
+    pub name: String,
+    pub since: Option<String>
+}
+
+#[derive(Debug,Clone)]
+pub struct Generated {
+    pub version: String,
+    pub compiled: Vec<String>,
+    pub skipped: Vec<String>,
+    pub corpus: Corpus
+}
+
+// "8.18.0" as [8,18,0], for comparing versions
+fn version_key(version: &str) -> Vec<u32> {
+    version.split(|c:char|!c.is_ascii_digit()).filter(|s|!s.is_empty()).map(|s|s.parse().unwrap_or(0)).collect()
+}
+
+pub fn at_least(version: &str, since: &str) -> bool {
+    version_key(version) >= version_key(since)
+}
+
+pub fn coqc_version(coqc: &Path) -> io::Result<String> {
+    let output = Command::new(coqc).arg("--print-version").output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!("{} --print-version failed", coqc.display())));
+    }
+    // the Coq version, then the OCaml version it was built with
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    stdout.split_whitespace().next().map(|v|v.to_string()).ok_or_else(||io::Error::other(format!("{} printed no version", coqc.display())))
+}
+
+// Every coqc or coqc-<anything> in the directories of a PATH-style list, one per Coq version
+pub fn find_coqcs(path_var: &OsStr) -> Vec<Coqc> {
+    let mut result: Vec<Coqc> = vec![];
+    for dir in env::split_paths(path_var) {
+        let mut candidates: Vec<PathBuf> = match read_dir(&dir) {
+            Ok(entries) => entries.filter_map(|e|e.ok()).map(|e|e.path()).filter(|p| {
+                p.file_name().and_then(|n|n.to_str()).is_some_and(|n|n == "coqc" || n.starts_with("coqc-"))
+            }).collect(),
+            Err(_) => continue
+        };
+        candidates.sort();
+        for path in candidates {
+            match coqc_version(&path) {
+                Ok(version) => if !result.iter().any(|c|c.version == version) {
+                    result.push(Coqc{path, version});
+                },
+                Err(e) => warn!("Skipping {}: {}", path.display(), e)
+            }
+        }
+    }
+    result.sort_by_key(|c|version_key(&c.version));
+    result
+}
+
+pub fn fixtures(sources: &Path) -> io::Result<Vec<Fixture>> {
+    let mut result = vec![];
+    for entry in read_dir(sources)? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e|e != "v") {
+            continue;
+        }
+        let name = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let text = read_to_string(&path)?;
+        let since = text.lines().next()
+            .and_then(|line|line.trim().strip_prefix("(* mathparse: since "))
+            .and_then(|rest|rest.strip_suffix("*)"))
+            .map(|v|v.trim().to_string());
+        result.push(Fixture{path, name, since});
+    }
+    result.sort_by(|a,b|a.name.cmp(&b.name));
+    Ok(result)
+}
+
+// Compiles the fixtures that coqc's version allows as Fixtures.<Name>, each on its own in a scratch
+// directory, copies the .vo files to out/<version>/ and writes expected.json there. Fails without
+// writing expected.json if any of them doesn't parse, rather than record the failure as expected.
+pub fn generate(coqc: &Coqc, fixtures: &[Fixture], out: &Path) -> io::Result<Generated> {
+    let build = env::temp_dir().join(format!("mathparse-fixtures-{}", coqc.version));
+    let dest = out.join(&coqc.version);
+    create_dir_all(&dest)?;
+    let mut compiled = vec![];
+    let mut skipped = vec![];
+    for fixture in fixtures {
+        if fixture.since.as_deref().is_some_and(|since|!at_least(&coqc.version, since)) {
+            skipped.push(fixture.name.clone());
+            continue;
+        }
+        if build.exists() {
+            remove_dir_all(&build)?;
+        }
+        create_dir_all(&build)?;
+        let source = build.join(format!("{}.v", fixture.name));
+        copy(&fixture.path, &source)?;
+        let status = Command::new(&coqc.path).arg("-Q").arg(&build).arg("Fixtures").arg(&source).status()?;
+        if !status.success() {
+            return Err(io::Error::other(format!("Coq {} failed to compile {}", coqc.version, fixture.path.display())));
+        }
+        copy(build.join(format!("{}.vo", fixture.name)), dest.join(format!("{}.vo", fixture.name)))?;
+        compiled.push(fixture.name.clone());
+    }
+    if build.exists() {
+        remove_dir_all(&build)?;
+    }
+    let corpus = run_corpus(&dest)?;
+    let failed: Vec<String> = corpus.results.iter().filter(|r|!r.ok)
+        .map(|r|format!("{} ({})", r.file, r.error.as_deref().unwrap_or("?"))).collect();
+    if !failed.is_empty() {
+        return Err(io::Error::other(format!("Coq {}: {} don't parse, so no expectations were recorded", coqc.version, failed.join(", "))));
+    }
+    serde_json::to_writer_pretty(File::create(dest.join("expected.json"))?, &corpus).map_err(io::Error::other)?;
+    Ok(Generated{version: coqc.version.clone(), compiled, skipped, corpus})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn versions_and_gates() {
+        assert!(at_least("8.18.0", "8.13"));
+        assert!(at_least("8.10.2", "8.10"));
+        assert!(!at_least("8.9.1", "8.10"));
+        assert!(at_least("8.20+rc1", "8.20"));
+        let sources = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("src");
+        let fixtures = fixtures(&sources).unwrap();
+        let array = fixtures.iter().find(|f|f.name == "PrimArray").unwrap();
+        assert_eq!(array.since.as_deref(), Some("8.13"));
+        assert_eq!(fixtures.iter().find(|f|f.name == "Basic").unwrap().since, None);
+    }
+}
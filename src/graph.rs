@@ -0,0 +1,175 @@
+use std::collections::{BTreeMap,BTreeSet};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::index::Index;
+
+#[derive(Debug,Clone,PartialEq,Serialize,JsonSchema)]
+pub struct LibraryDegree {
+    pub name: String,
+    // Indexed libraries that depend on this one
+    pub in_degree: usize,
+    // Libraries this one depends on, whether indexed or not
+    pub out_degree: usize
+}
+
+// Metrics of the dependency graph between the libraries in an index, with an edge from each
+// library to every dependency recorded in its summary
+#[derive(Debug,Clone,Default,PartialEq,Serialize,JsonSchema)]
+pub struct GraphMetrics {
+    pub libraries: Vec<LibraryDegree>,
+    // A longest path, each library depending on the next
+    pub longest_chain: Vec<String>,
+    // Strongly connected components with more than one library (or a library depending on itself)
+    pub cycles: Vec<Vec<String>>,
+    // Indexed libraries that no other indexed library depends on
+    pub unused: Vec<String>
+}
+
+struct Graph<'a> {
+    edges: BTreeMap<&'a str,BTreeSet<&'a str>>
+}
+
+impl<'a> Graph<'a> {
+    fn new(index: &'a Index) -> Self {
+        let mut edges: BTreeMap<&str,BTreeSet<&str>> = BTreeMap::new();
+        for entry in &index.libraries {
+            let deps = edges.entry(entry.name.as_str()).or_default();
+            deps.extend(entry.deps.iter().map(|d|d.name.as_str()));
+        }
+        for entry in &index.libraries {
+            for dep in &entry.deps {
+                edges.entry(dep.name.as_str()).or_default();
+            }
+        }
+        Graph{edges}
+    }
+
+    fn deps(&self, name: &str) -> impl Iterator<Item=&'a str> + '_ {
+        self.edges[name].iter().copied()
+    }
+
+    // Tarjan's algorithm; components come out in reverse topological order
+    fn components(&self) -> Vec<Vec<&'a str>> {
+        struct State<'a> {
+            next: usize,
+            index: BTreeMap<&'a str,usize>,
+            low: BTreeMap<&'a str,usize>,
+            stack: Vec<&'a str>,
+            on_stack: BTreeSet<&'a str>,
+            result: Vec<Vec<&'a str>>
+        }
+        fn visit<'a>(g: &Graph<'a>, v: &'a str, s: &mut State<'a>) {
+            s.index.insert(v, s.next);
+            s.low.insert(v, s.next);
+            s.next += 1;
+            s.stack.push(v);
+            s.on_stack.insert(v);
+            for w in g.deps(v) {
+                if !s.index.contains_key(w) {
+                    visit(g, w, s);
+                    let low = s.low[v].min(s.low[w]);
+                    s.low.insert(v, low);
+                } else if s.on_stack.contains(w) {
+                    let low = s.low[v].min(s.index[w]);
+                    s.low.insert(v, low);
+                }
+            }
+            if s.low[v] == s.index[v] {
+                let mut component = vec![];
+                while let Some(w) = s.stack.pop() {
+                    s.on_stack.remove(w);
+                    component.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                component.sort();
+                s.result.push(component);
+            }
+        }
+        let mut s = State{next:0, index:BTreeMap::new(), low:BTreeMap::new(), stack:vec![], on_stack:BTreeSet::new(), result:vec![]};
+        for &v in self.edges.keys() {
+            if !s.index.contains_key(v) {
+                visit(self, v, &mut s);
+            }
+        }
+        s.result
+    }
+}
+
+pub fn graph_metrics(index: &Index) -> GraphMetrics {
+    let graph = Graph::new(index);
+    let indexed: BTreeSet<&str> = index.libraries.iter().map(|e|e.name.as_str()).collect();
+    let mut in_degree: BTreeMap<&str,usize> = BTreeMap::new();
+    for &name in &indexed {
+        for dep in graph.deps(name) {
+            *in_degree.entry(dep).or_insert(0) += 1;
+        }
+    }
+    let libraries = indexed.iter().map(|&name|LibraryDegree {
+        name: name.to_string(),
+        in_degree: in_degree.get(name).copied().unwrap_or(0),
+        out_degree: graph.edges[name].len()
+    }).collect();
+    let unused = indexed.iter().filter(|&&n|!in_degree.contains_key(n)).map(|n|n.to_string()).collect();
+
+    let components = graph.components();
+    let cycles = components.iter()
+        .filter(|c|c.len() > 1 || graph.edges[c[0]].contains(c[0]))
+        .map(|c|c.iter().map(|n|n.to_string()).collect())
+        .collect();
+
+    // Longest chain over the components, which arrive dependencies first. Edges inside a cycle are ignored.
+    let component_of: BTreeMap<&str,usize> = components.iter().enumerate().flat_map(|(k,c)|c.iter().map(move|&n|(n,k))).collect();
+    let mut best: BTreeMap<&str,(usize,Option<&str>)> = BTreeMap::new();
+    for component in &components {
+        for &v in component {
+            let next = graph.deps(v)
+                .filter(|w|component_of[w] != component_of[v])
+                .max_by(|a,b|best[a].0.cmp(&best[b].0).then_with(||b.cmp(a)));
+            best.insert(v, (next.map_or(1, |w|best[w].0 + 1), next));
+        }
+    }
+    let mut longest_chain = vec![];
+    let mut current = best.iter().max_by(|a,b|a.1.0.cmp(&b.1.0).then_with(||b.0.cmp(a.0))).map(|(&n,_)|n);
+    while let Some(v) = current {
+        longest_chain.push(v.to_string());
+        current = best[v].1;
+    }
+
+    GraphMetrics{libraries, longest_chain, cycles, unused}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{IndexDep,IndexEntry};
+
+    fn entry(name: &str, deps: &[&str]) -> IndexEntry {
+        IndexEntry {
+            file: format!("{}.vo", name), name: name.to_string(), digest: None, imports: vec![],
+            deps: deps.iter().map(|d|IndexDep{name:d.to_string(), digest:String::new()}).collect(),
+            segments: vec![], size: 0, mtime: 0
+        }
+    }
+
+    #[test]
+    fn metrics() {
+        let index = Index{meta: None, libraries: vec![
+            entry("A", &["Coq.Init"]),
+            entry("B", &["A", "Coq.Init"]),
+            entry("C", &["B", "A"]),
+            entry("D", &["A"]),
+            entry("X", &["Y"]),
+            entry("Y", &["X"])
+        ]};
+        let m = graph_metrics(&index);
+        assert_eq!(m.longest_chain, vec!["C", "B", "A", "Coq.Init"]);
+        assert_eq!(m.cycles, vec![vec!["X".to_string(), "Y".to_string()]]);
+        assert_eq!(m.unused, vec!["C", "D"]);
+        let a = m.libraries.iter().find(|l|l.name == "A").unwrap();
+        assert_eq!((a.in_degree, a.out_degree), (3, 1));
+    }
+}
@@ -0,0 +1,283 @@
+use std::collections::{HashMap,HashSet};
+use std::fs::{metadata,read,read_dir};
+use std::io;
+use std::path::{Path,PathBuf};
+use std::time::UNIX_EPOCH;
+
+use schemars::JsonSchema;
+use serde::{Deserialize,Serialize};
+
+use crate::loadpath::{absolute,relative_name};
+use crate::meta::Meta;
+use crate::parse::{file,ParseOptions,VoFile};
+
+// A dependency and the digest it had when this library was compiled
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize,JsonSchema)]
+pub struct IndexDep {
+    pub name: String,
+    pub digest: String
+}
+
+#[derive(Debug,Clone,Serialize,Deserialize,JsonSchema)]
+pub struct IndexEntry {
+    pub file: String,
+    pub name: String,
+    pub digest: Option<String>,
+    pub imports: Vec<String>,
+    #[serde(default)]
+    pub deps: Vec<IndexDep>,
+    pub segments: Vec<usize>,
+    // Used to decide whether an entry can be reused by update_index
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub mtime: u64
+}
+
+impl IndexEntry {
+    pub fn new(file: String, vo: &VoFile) -> Self {
+        IndexEntry {
+            file,
+            size: 0,
+            mtime: 0,
+            name: vo.summary.name().to_string(),
+            digest: vo.library_digest().map(|d|d.to_hex()),
+            imports: vo.summary.imports().iter().map(|d|d.to_string()).collect(),
+            deps: vo.summary.deps().iter().map(|(d,digest)|IndexDep{name:d.to_string(), digest:digest.to_hex()}).collect(),
+            segments: vo.segments.iter().map(|s|s.len).collect()
+        }
+    }
+    pub fn same_contents(&self, other: &IndexEntry) -> bool {
+        self.name == other.name && self.digest == other.digest && self.imports == other.imports && self.deps == other.deps && self.segments == other.segments
+    }
+}
+
+#[derive(Debug,Clone,Default,Serialize,Deserialize,JsonSchema)]
+pub struct Index {
+    // Missing from indexes written before it was added, which are treated as out of date
+    #[serde(default)]
+    pub meta: Option<Meta>,
+    pub libraries: Vec<IndexEntry>
+}
+
+impl Index {
+    // The library whose logical name is the longest proper prefix of qualid, e.g. Coq.Init.Nat.add -> Coq.Init.Nat.
+    // Constant names aren't indexed yet, so this says where the name would live, not that it is actually there.
+    pub fn resolve(&self, qualid: &str) -> Option<&IndexEntry> {
+        self.libraries.iter()
+            .filter(|e|qualid.len() > e.name.len() && qualid.starts_with(&e.name) && qualid[e.name.len()..].starts_with('.'))
+            .max_by_key(|e|e.name.len())
+    }
+}
+
+// All .vo files below dir, sorted so that the index comes out in a stable order
+pub fn find_vo_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut result = vec![];
+    let mut todo = vec![dir.to_path_buf()];
+    // directories already listed, by where symlinks lead, so a link back up the tree isn't followed forever
+    let mut seen = HashSet::new();
+    while let Some(d) = todo.pop() {
+        if !seen.insert(absolute(&d)) {
+            continue;
+        }
+        for entry in read_dir(&d)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                todo.push(path);
+            } else if path.extension().is_some_and(|e|e.eq_ignore_ascii_case("vo")) {
+                result.push(path);
+            }
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn size_and_mtime(path: &Path) -> io::Result<(u64,u64)> {
+    let meta = metadata(path)?;
+    let mtime = meta.modified()?.duration_since(UNIX_EPOCH).map(|d|d.as_secs()).unwrap_or(0);
+    Ok((meta.len(), mtime))
+}
+
+pub fn index_entry(dir: &Path, path: &Path) -> io::Result<Option<IndexEntry>> {
+    let (size,mtime) = size_and_mtime(path)?;
+    let contents = read(path)?;
+    match file(&contents) {
+        Ok((_,vo)) => {
+            let mut entry = IndexEntry::new(relative_name(dir, path), &vo);
+            entry.size = size;
+            entry.mtime = mtime;
+            Ok(Some(entry))
+        }
+        Err(e) => {
+            warn!("Skipping {}: {:?}", path.display(), e);
+            Ok(None)
+        }
+    }
+}
+
+pub fn build_index(dir: &Path) -> io::Result<Index> {
+    update_index(dir, &Index::default()).map(|(index,_)|index)
+}
+
+// Library names, by what happened to them since the previous index. failed is for files that are
+// still there but no longer parse, and so have no entry in the new index.
+#[derive(Debug,Clone,Default,Serialize,Deserialize,JsonSchema)]
+pub struct IndexChanges {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub modified: Vec<String>,
+    #[serde(default)]
+    pub failed: Vec<String>
+}
+
+impl IndexChanges {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty() && self.failed.is_empty()
+    }
+}
+
+// Like build_index, but entries from previous whose file has the same size and mtime are reused without
+// reparsing, as long as previous was written by this version of mathparse
+pub fn update_index(dir: &Path, previous: &Index) -> io::Result<(Index,IndexChanges)> {
+    let options = ParseOptions::default();
+    let reusable = previous.meta.as_ref().is_some_and(|m|m.is_current(&options));
+    let mut old: HashMap<&str,&IndexEntry> = previous.libraries.iter().map(|e|(e.file.as_str(),e)).collect();
+    let mut libraries = vec![];
+    let mut changes = IndexChanges::default();
+    for path in find_vo_files(dir)? {
+        let rel = relative_name(dir, &path);
+        let old_entry = old.remove(rel.as_str());
+        if let Some(old_entry) = old_entry {
+            if reusable && size_and_mtime(&path)? == (old_entry.size, old_entry.mtime) {
+                libraries.push(old_entry.clone());
+                continue;
+            }
+        }
+        match (old_entry, index_entry(dir, &path)?) {
+            (None, Some(entry)) => {
+                changes.added.push(entry.name.clone());
+                libraries.push(entry);
+            }
+            (Some(old_entry), Some(entry)) => {
+                if !old_entry.same_contents(&entry) {
+                    changes.modified.push(entry.name.clone());
+                }
+                libraries.push(entry);
+            }
+            (Some(old_entry), None) => changes.failed.push(old_entry.name.clone()),
+            (None, None) => {}
+        }
+    }
+    changes.removed.extend(old.into_values().map(|e|e.name.clone()));
+    changes.added.sort();
+    changes.removed.sort();
+    changes.modified.sort();
+    changes.failed.sort();
+    Ok((Index{meta:Some(Meta::new(&options)), libraries},changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all,remove_dir_all,write};
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mathparse-{}-{}", name, std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(file: &str, name: &str) -> IndexEntry {
+        IndexEntry{file:file.to_string(), name:name.to_string(), digest:None, imports:vec![], deps:vec![], segments:vec![], size:0, mtime:0}
+    }
+
+    #[test]
+    fn resolve_longest_prefix() {
+        let index = Index{meta: None, libraries: vec![entry("a.vo","A"), entry("a/b.vo","A.B"), entry("a/b/c.vo","A.B.C")]};
+        let resolved = |qualid|index.resolve(qualid).map(|e|e.file.as_str());
+        assert_eq!(resolved("A.B.C.x"), Some("a/b/c.vo"));
+        assert_eq!(resolved("A.B.x"), Some("a/b.vo"));
+        // prefixes only count up to a '.', so A.BC is in A, not A.B
+        assert_eq!(resolved("A.BC.x"), Some("a.vo"));
+        // a library's own name isn't a name inside it
+        assert_eq!(resolved("A.B"), Some("a.vo"));
+        assert_eq!(resolved("A"), None);
+        assert_eq!(resolved("AB.x"), None);
+        assert_eq!(resolved("Coq.Init.Nat.add"), None);
+    }
+
+    #[test]
+    fn vo_files_are_sorted() {
+        let dir = scratch_dir("sorted");
+        for f in &["z.vo", "b/y.vo", "a.vo", "b/a.vo", "c/d/e.vo", "a.v"] {
+            let path = dir.join(f);
+            create_dir_all(path.parent().unwrap()).unwrap();
+            write(path, b"").unwrap();
+        }
+        let found: Vec<String> = find_vo_files(&dir).unwrap().iter().map(|p|relative_name(&dir, p)).collect();
+        assert_eq!(found, vec!["a.vo", "b/a.vo", "b/y.vo", "c/d/e.vo", "z.vo"]);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlink_cycles() {
+        let dir = scratch_dir("symlinks");
+        create_dir_all(dir.join("a")).unwrap();
+        write(dir.join("a/x.vo"), b"").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("a/up")).unwrap();
+        let found: Vec<String> = find_vo_files(&dir).unwrap().iter().map(|p|relative_name(&dir, p)).collect();
+        assert_eq!(found, vec!["a/x.vo"]);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn removed_libraries_are_sorted() {
+        let dir = scratch_dir("removed");
+        let previous = Index{meta: None, libraries: vec![entry("q.vo","Q"), entry("a/c.vo","A.C"), entry("b.vo","B"), entry("a/b.vo","A.B")]};
+        let (index,changes) = update_index(&dir, &previous).unwrap();
+        assert!(index.libraries.is_empty());
+        assert_eq!(changes.removed, vec!["A.B", "A.C", "B", "Q"]);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn entries_from_other_versions_are_reparsed() {
+        let dir = scratch_dir("meta");
+        write(dir.join("s.vo"), &include_bytes!("selftest.vo")[..]).unwrap();
+        let mut previous = build_index(&dir).unwrap();
+        previous.libraries[0].name = "Renamed".to_string();
+        let (index,_) = update_index(&dir, &previous).unwrap();
+        assert_eq!(index.libraries[0].name, "Renamed");
+        previous.meta.as_mut().unwrap().mathparse_version = "0.0.0".to_string();
+        let (index,changes) = update_index(&dir, &previous).unwrap();
+        assert_eq!(index.libraries[0].name, "Mathparse.Selftest");
+        assert_eq!(changes.modified, vec!["Mathparse.Selftest"]);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn changed_files_are_modified_or_failed() {
+        let dir = scratch_dir("changed");
+        write(dir.join("s.vo"), &include_bytes!("selftest.vo")[..]).unwrap();
+        // an entry whose size no longer matches is reparsed, and differs in its digest
+        let mut previous = build_index(&dir).unwrap();
+        previous.libraries[0].size = 0;
+        previous.libraries[0].digest = Some("00".to_string());
+        let (index,changes) = update_index(&dir, &previous).unwrap();
+        assert_eq!(changes.modified, vec!["Mathparse.Selftest"]);
+        assert!(changes.added.is_empty() && changes.removed.is_empty() && changes.failed.is_empty());
+        assert_eq!(index.libraries[0].digest, build_index(&dir).unwrap().libraries[0].digest);
+        // the file is still there but no longer parses
+        write(dir.join("s.vo"), b"not a .vo file").unwrap();
+        let (index,changes) = update_index(&dir, &index).unwrap();
+        assert!(index.libraries.is_empty());
+        assert_eq!(changes.failed, vec!["Mathparse.Selftest"]);
+        assert!(changes.removed.is_empty());
+        remove_dir_all(&dir).unwrap();
+    }
+}
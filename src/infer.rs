@@ -0,0 +1,322 @@
+// Suggests Rust types for marshalled data that has no typed parser yet, from what is observed in
+// its untyped value tree. The output is a starting point to be edited, not something to trust.
+
+use std::collections::{BTreeMap,BTreeSet};
+use std::fmt::Write;
+
+use crate::parse::generic::Value;
+
+// Positions deeper than this are summarised without looking inside
+const MAX_DEPTH: usize = 16;
+// Stop observing after this many values, since shared subtrees are visited once per use
+const MAX_VALUES: usize = 1_000_000;
+// Beyond this many distinct ints a position is just "some ints"
+const MAX_INTS: usize = 16;
+
+// Everything seen at one position in the tree, over every value found there
+#[derive(Debug,Default)]
+struct Observed {
+    count: usize,
+    ints: BTreeSet<i64>,
+    many_ints: bool,
+    strings: usize,
+    doubles: usize,
+    int63s: usize,
+    // kinds of other scalars, e.g. "int32"
+    others: BTreeSet<&'static str>,
+    cycles: usize,
+    too_deep: usize,
+    // fields by tag, then by block length
+    blocks: BTreeMap<u8,BTreeMap<usize,Vec<Observed>>>,
+    // the elements, if lists were seen here
+    list: Option<Box<Observed>>
+}
+
+struct Observer {
+    budget: usize
+}
+
+// The elements, if v is a non-empty OCaml list: blocks of tag 0 and length 2 chained through
+// their second field and ending in the int 0
+fn list_elements(v: &Value) -> Option<Vec<&Value>> {
+    let mut elements = vec![];
+    let mut v = v;
+    loop {
+        match v {
+            Value::Block{tag:0, fields} if fields.len() == 2 => {
+                elements.push(&*fields[0]);
+                v = &fields[1];
+            }
+            Value::Int(0) if !elements.is_empty() => return Some(elements),
+            _ => return None
+        }
+    }
+}
+
+impl Observer {
+    fn observe(&mut self, v: &Value, obs: &mut Observed, depth: usize) {
+        if self.budget == 0 {
+            return;
+        }
+        self.budget -= 1;
+        obs.count += 1;
+        if depth > MAX_DEPTH {
+            obs.too_deep += 1;
+            return;
+        }
+        match v {
+            Value::Int(n) => {
+                if obs.ints.len() < MAX_INTS {
+                    obs.ints.insert(*n);
+                } else if !obs.ints.contains(n) {
+                    obs.many_ints = true;
+                }
+            }
+            Value::String(_) => obs.strings += 1,
+            Value::Double(_) => obs.doubles += 1,
+            Value::Int63(_) => obs.int63s += 1,
+            Value::Int32(_) => { obs.others.insert("int32"); }
+            Value::Int64(_) => { obs.others.insert("int64"); }
+            Value::NativeInt(_) => { obs.others.insert("nativeint"); }
+            Value::BigInt(_) => { obs.others.insert("bigint"); }
+            Value::Custom{..} => { obs.others.insert("custom block"); }
+            Value::CodePointer{..} => { obs.others.insert("code pointer"); }
+            Value::Cycle(_) => obs.cycles += 1,
+            Value::Block{tag, fields} => {
+                if let Some(elements) = list_elements(v) {
+                    let list = obs.list.get_or_insert_with(Default::default);
+                    for e in elements {
+                        self.observe(e, list, depth + 1);
+                    }
+                    return;
+                }
+                let by_len = obs.blocks.entry(*tag).or_default();
+                let field_obs = by_len.entry(fields.len()).or_insert_with(||fields.iter().map(|_|Observed::default()).collect());
+                for (f,o) in fields.iter().zip(field_obs.iter_mut()) {
+                    self.observe(f, o, depth + 1);
+                }
+            }
+        }
+    }
+}
+
+impl Observed {
+    fn merge(&mut self, other: Observed) {
+        self.count += other.count;
+        for n in other.ints {
+            if self.ints.len() < MAX_INTS {
+                self.ints.insert(n);
+            } else if !self.ints.contains(&n) {
+                self.many_ints = true;
+            }
+        }
+        self.many_ints |= other.many_ints;
+        self.strings += other.strings;
+        self.doubles += other.doubles;
+        self.int63s += other.int63s;
+        self.others.extend(other.others);
+        self.cycles += other.cycles;
+        self.too_deep += other.too_deep;
+        for (tag,by_len) in other.blocks {
+            let mine = self.blocks.entry(tag).or_default();
+            for (len,fields) in by_len {
+                match mine.get_mut(&len) {
+                    Some(existing) => {
+                        for (a,b) in existing.iter_mut().zip(fields) {
+                            a.merge(b);
+                        }
+                    }
+                    None => {
+                        mine.insert(len, fields);
+                    }
+                }
+            }
+        }
+        if let Some(list) = other.list {
+            match &mut self.list {
+                Some(mine) => mine.merge(*list),
+                None => self.list = Some(list)
+            }
+        }
+    }
+    fn has_scalars(&self) -> bool {
+        self.strings > 0 || self.doubles > 0 || self.int63s > 0 || !self.others.is_empty()
+    }
+}
+
+// Builds up the definitions, each named after its position in the tree
+struct Printer {
+    definitions: Vec<String>
+}
+
+fn plural(n: usize, what: &str) -> String {
+    if n == 1 { format!("1 {}", what) } else { format!("{} {}s", n, what) }
+}
+
+impl Printer {
+    // A type for the values seen at obs, with a note on what that's based on
+    fn type_of(&mut self, mut obs: Observed, name: &str) -> (String,String) {
+        let only_ints = obs.blocks.is_empty() && obs.list.is_none() && !obs.has_scalars();
+        let only_blocks = obs.ints.is_empty() && !obs.many_ints && obs.list.is_none() && !obs.has_scalars();
+        if obs.count == 0 {
+            return ("()".to_string(), "never seen".to_string());
+        }
+        if obs.too_deep > 0 || obs.cycles > 0 {
+            return ("generic::Value".to_string(), format!("{} too deep to look into, {} cyclic", obs.too_deep, obs.cycles));
+        }
+        if only_ints {
+            let values: Vec<String> = obs.ints.iter().map(|n|n.to_string()).collect();
+            let seen = if obs.many_ints { "many values".to_string() } else { format!("values {}", values.join(", ")) };
+            if !obs.many_ints && obs.ints.iter().all(|&n|n == 0 || n == 1) && obs.ints.len() == 2 {
+                return ("bool".to_string(), seen);
+            }
+            if !obs.many_ints && obs.ints.len() == 1 && obs.ints.contains(&0) {
+                return ("i64".to_string(), "only 0 seen: maybe an empty list, None or a constant constructor".to_string());
+            }
+            return ("i64".to_string(), seen);
+        }
+        if obs.blocks.is_empty() && obs.list.is_none() && obs.ints.is_empty() && !obs.many_ints {
+            let kinds = [(obs.strings, "String"), (obs.doubles, "f64"), (obs.int63s, "Uint63")];
+            let seen: Vec<&str> = kinds.iter().filter(|(n,_)|*n > 0).map(|(_,t)|*t).collect();
+            if seen.len() == 1 && obs.others.is_empty() {
+                return (seen[0].to_string(), String::new());
+            }
+        }
+        if let Some(list) = obs.list.take() {
+            if obs.blocks.is_empty() && !obs.has_scalars() && !obs.many_ints && obs.ints.iter().all(|&n|n == 0) {
+                let (elem,note) = self.type_of(*list, &format!("{}Item", name));
+                let note = if note.is_empty() { "OCaml list".to_string() } else { format!("OCaml list; elements: {}", note) };
+                return (format!("List<{}>", elem), note);
+            }
+            return ("generic::Value".to_string(), "lists mixed with other values".to_string());
+        }
+        // None and Some
+        if !obs.has_scalars() && !obs.many_ints && obs.ints.iter().all(|&n|n == 0) && obs.blocks.len() == 1 {
+            if let Some(by_len) = obs.blocks.get(&0) {
+                if by_len.len() == 1 && by_len.contains_key(&1) && !obs.ints.is_empty() {
+                    let mut by_len = obs.blocks.remove(&0).unwrap();
+                    let inner = by_len.remove(&1).unwrap().pop().unwrap();
+                    let (t,note) = self.type_of(inner, &format!("{}Inner", name));
+                    return (format!("Option<{}>", t), note);
+                }
+            }
+        }
+        if only_blocks && obs.blocks.len() == 1 {
+            if let Some(by_len) = obs.blocks.remove(&0) {
+                let count = obs.count;
+                if by_len.len() == 1 {
+                    let (len,fields) = by_len.into_iter().next().unwrap();
+                    self.structure(name, len, count, fields);
+                    return (name.to_string(), String::new());
+                }
+                // blocks of several lengths: an array
+                let lengths: Vec<String> = by_len.keys().map(|n|n.to_string()).collect();
+                let mut elements = Observed::default();
+                for fields in by_len.into_values() {
+                    for f in fields {
+                        elements.merge(f);
+                    }
+                }
+                let (elem,note) = self.type_of(elements, &format!("{}Item", name));
+                let note = format!("array, lengths {}{}", lengths.join(", "), if note.is_empty() { String::new() } else { format!("; elements: {}", note) });
+                return (format!("Vec<{}>", elem), note);
+            }
+        }
+        if !obs.has_scalars() && !obs.many_ints {
+            self.enumeration(name, obs);
+            return (name.to_string(), String::new());
+        }
+        ("generic::Value".to_string(), "mixed kinds of value".to_string())
+    }
+
+    fn field_line(&mut self, out: &mut String, field: &str, obs: Observed, name: &str) {
+        let (t,note) = self.type_of(obs, name);
+        if note.is_empty() {
+            writeln!(out, "    pub {}: {},", field, t).unwrap();
+        } else {
+            writeln!(out, "    pub {}: {}, // {}", field, t, note).unwrap();
+        }
+    }
+
+    fn structure(&mut self, name: &str, len: usize, count: usize, fields: Vec<Observed>) {
+        let mut out = String::new();
+        writeln!(out, "// block of tag 0 with {}, seen {}", plural(len, "field"), plural(count, "time")).unwrap();
+        writeln!(out, "#[derive(Debug,Clone,VoParse)]").unwrap();
+        writeln!(out, "pub struct {} {{", name).unwrap();
+        for (k,f) in fields.into_iter().enumerate() {
+            self.field_line(&mut out, &format!("f{}", k), f, &format!("{}{}", name, k));
+        }
+        out.push('}');
+        self.definitions.push(out);
+    }
+
+    fn enumeration(&mut self, name: &str, obs: Observed) {
+        let mut out = String::new();
+        writeln!(out, "// variant, seen {}: parse with variant()", plural(obs.count, "time")).unwrap();
+        writeln!(out, "#[derive(Debug,Clone)]").unwrap();
+        writeln!(out, "pub enum {} {{", name).unwrap();
+        let mut lines = vec![];
+        for n in &obs.ints {
+            lines.push(format!("    C{}", n));
+        }
+        for (tag,by_len) in obs.blocks {
+            for (len,fields) in by_len {
+                let variant = format!("T{}", tag);
+                let mut types = vec![];
+                for (k,f) in fields.into_iter().enumerate() {
+                    let (t,note) = self.type_of(f, &format!("{}{}_{}", name, variant, k));
+                    types.push(if note.is_empty() { t } else { format!("{} /* {} */", t, note) });
+                }
+                lines.push(format!("    {}({}) /* {} */", variant, types.join(", "), plural(len, "field")));
+            }
+        }
+        out.push_str(&lines.join(",\n"));
+        out.push_str("\n}");
+        self.definitions.push(out);
+    }
+}
+
+// Rust definitions for the values seen in a segment, the one for the whole segment called name.
+// Definitions come out innermost first, as they are completed.
+pub fn infer_types(value: &Value, name: &str) -> String {
+    let mut root = Observed::default();
+    Observer{budget:MAX_VALUES}.observe(value, &mut root, 0);
+    let mut printer = Printer{definitions:vec![]};
+    let (t,note) = printer.type_of(root, name);
+    if t != name {
+        printer.definitions.push(format!("pub type {} = {};{}", name, t, if note.is_empty() { String::new() } else { format!(" // {}", note) }));
+    }
+    printer.definitions.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::generic::file_values;
+
+    #[test]
+    fn summary_types() {
+        let values = file_values(include_bytes!("selftest.vo"));
+        let types = infer_types(values[0].as_ref().unwrap(), "Summary");
+        assert!(types.contains("pub struct Summary {"), "{}", types);
+        assert!(types.contains("    pub f0: List<String>, // OCaml list"), "{}", types);
+        assert_eq!(infer_types(values[1].as_ref().unwrap(), "Library"), "pub type Library = i64; // values 1");
+    }
+
+    #[test]
+    fn variants_and_options() {
+        use std::rc::Rc;
+        let block = |tag, fields: Vec<Value>|Value::Block{tag, fields:fields.into_iter().map(Rc::new).collect()};
+        // the list [C0; T1 "s"; C1; T0 (Some 2.0); T0 None]
+        let elements = vec![
+            Value::Int(0),
+            block(1, vec![Value::String(b"s".to_vec())]),
+            Value::Int(1),
+            block(0, vec![block(0, vec![Value::Double(2.0)])]),
+            block(0, vec![Value::Int(0)])
+        ];
+        let list = elements.into_iter().rev().fold(Value::Int(0), |tail,hd|block(0, vec![hd, tail]));
+        let types = infer_types(&list, "L");
+        assert_eq!(types, "// variant, seen 5 times: parse with variant()\n#[derive(Debug,Clone)]\npub enum LItem {\n    C0,\n    C1,\n    T0(Option<f64>) /* 1 field */,\n    T1(String) /* 1 field */\n}\n\npub type L = List<LItem>; // OCaml list");
+    }
+}
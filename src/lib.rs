@@ -0,0 +1,29 @@
+#[macro_use]
+extern crate log;
+// so #[derive(VoParse)] can use the same paths here as in other crates
+extern crate self as mathparse;
+
+pub mod batch;
+pub mod corpus;
+pub mod diff;
+pub mod dump;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+pub mod graph;
+pub mod index;
+pub mod infer;
+pub mod loadpath;
+pub mod locate;
+pub mod meta;
+pub mod native;
+pub mod parse;
+pub mod prelude;
+pub mod repro;
+pub mod selftest;
+pub mod snapshot;
+pub mod split;
+pub mod stale;
+pub mod stats;
+pub mod testing;
+pub mod triage;
+pub mod types;
@@ -0,0 +1,105 @@
+use std::env::current_dir;
+use std::path::{Component,Path,PathBuf};
+
+// A directory mapped to a logical prefix, as with coqc -Q DIR PREFIX
+#[derive(Debug,Clone)]
+pub struct LoadPath {
+    pub dir: PathBuf,
+    pub prefix: String
+}
+
+// Whether names differing only in case are the same file, as on Windows and (by default) macOS
+pub const CASE_INSENSITIVE: bool = cfg!(any(windows, target_os = "macos"));
+
+// Windows canonicalizes to \\?\C:\..., which no path the user types will start with
+fn strip_verbatim(path: PathBuf) -> PathBuf {
+    match path.to_str().and_then(|s|s.strip_prefix(r"\\?\")) {
+        Some(rest) if !rest.starts_with(r"UNC\") => PathBuf::from(rest),
+        _ => path
+    }
+}
+
+// An absolute path with symlinks resolved where it exists, or with . and .. worked out from the
+// text where it doesn't
+pub fn absolute(path: &Path) -> PathBuf {
+    if let Ok(canonical) = path.canonicalize() {
+        return strip_verbatim(canonical);
+    }
+    let path = if path.is_absolute() { path.to_path_buf() } else { current_dir().map(|d|d.join(path)).unwrap_or_else(|_|path.to_path_buf()) };
+    let mut result = PathBuf::new();
+    for c in path.components() {
+        match c {
+            Component::CurDir => {}
+            Component::ParentDir => { result.pop(); }
+            c => result.push(c)
+        }
+    }
+    result
+}
+
+// The components of path below dir, if it is below dir
+fn relative_components(dir: &Path, path: &Path, ignore_case: bool) -> Option<Vec<String>> {
+    let mut path = path.components();
+    for d in dir.components() {
+        let p = path.next()?;
+        let (d,p) = (d.as_os_str().to_string_lossy(), p.as_os_str().to_string_lossy());
+        if !(d == p || ignore_case && d.to_lowercase() == p.to_lowercase()) {
+            return None;
+        }
+    }
+    Some(path.map(|c|c.as_os_str().to_string_lossy().into_owned()).collect())
+}
+
+// path relative to dir with / between components on every platform, so indexes and corpus
+// results can be shared. The whole path if it isn't below dir.
+pub fn relative_name(dir: &Path, path: &Path) -> String {
+    match relative_components(dir, path, CASE_INSENSITIVE) {
+        Some(parts) => parts.join("/"),
+        None => path.to_string_lossy().into_owned()
+    }
+}
+
+fn expected_name_with_case(loadpaths: &[LoadPath], vo_path: &Path, ignore_case: bool) -> Option<String> {
+    let vo_path = absolute(vo_path).with_extension("");
+    for lp in loadpaths {
+        if let Some(rel) = relative_components(&absolute(&lp.dir), &vo_path, ignore_case) {
+            let mut parts: Vec<String> = lp.prefix.split('.').filter(|s|!s.is_empty()).map(|s|s.to_string()).collect();
+            parts.extend(rel);
+            return Some(parts.join("."));
+        }
+    }
+    None
+}
+
+// The logical name a .vo file should have, from the first loadpath whose directory contains it.
+// None if no loadpath does.
+pub fn expected_name(loadpaths: &[LoadPath], vo_path: &Path) -> Option<String> {
+    expected_name_with_case(loadpaths, vo_path, CASE_INSENSITIVE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_from_loadpaths() {
+        let loadpaths = vec![
+            LoadPath{dir: PathBuf::from("/nonexistent/theories"), prefix: "Foo".to_string()},
+            LoadPath{dir: PathBuf::from("/nonexistent/other"), prefix: "".to_string()}
+        ];
+        assert_eq!(expected_name(&loadpaths, Path::new("/nonexistent/theories/Bar/Baz.vo")), Some("Foo.Bar.Baz".to_string()));
+        assert_eq!(expected_name(&loadpaths, Path::new("/nonexistent/other/A.vo")), Some("A".to_string()));
+        assert_eq!(expected_name(&loadpaths, Path::new("/elsewhere/A.vo")), None);
+        assert_eq!(expected_name(&loadpaths, Path::new("/nonexistent/other/../theories/./C.vo")), Some("Foo.C".to_string()));
+    }
+
+    #[test]
+    fn case_and_separators() {
+        let loadpaths = vec![LoadPath{dir: PathBuf::from("/nonexistent/Theories"), prefix: "Foo".to_string()}];
+        let vo = Path::new("/nonexistent/theories/Bar.vo");
+        assert_eq!(expected_name_with_case(&loadpaths, vo, true), Some("Foo.Bar".to_string()));
+        assert_eq!(expected_name_with_case(&loadpaths, vo, false), None);
+        assert_eq!(relative_name(Path::new("/a/b"), Path::new("/a/b/c/d.vo")), "c/d.vo");
+        assert_eq!(relative_name(Path::new("/a/b"), Path::new("/a/bc/d.vo")), "/a/bc/d.vo");
+    }
+}
@@ -0,0 +1,219 @@
+use std::ops::Range;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::parse::{marshal_header,md5,HeaderKind,ObjectIter,Repr};
+use crate::split::segment_ranges;
+use crate::types::DigestBytes;
+
+// Where a segment whose digest doesn't match can have been damaged. range is in file offsets.
+// MD5 can't be bisected, so this comes from walking the object structure: anything that makes
+// it inconsistent must lie at or before the point where it stops making sense.
+#[derive(Debug,Clone,PartialEq,Serialize,JsonSchema)]
+pub struct Corruption {
+    pub segment: usize,
+    pub range: Range<usize>,
+    pub reason: String
+}
+
+// The offset (within data) just past the first structural inconsistency, and what it was.
+// None if the structure is intact, in which case only scalar contents can have changed.
+fn first_inconsistency(data: &[u8], objects: u64) -> Option<(usize,String)> {
+    let mut pending = vec![1usize];
+    let mut cells = 0;
+    let mut iter = ObjectIter::new(data).peekable();
+    while let Some(object) = iter.next() {
+        let end = match iter.peek() {
+            Some(Ok((offset,_))) => *offset,
+            _ => data.len()
+        };
+        let repr = match object {
+            Ok((_,repr)) => repr,
+            Err(e) => {
+                let offset = data.len() - e.stuff.first().map(|(rest,_)|*rest).unwrap_or(0);
+                return Some((offset.min(data.len()), "undecodable object".to_string()));
+            }
+        };
+        if let Some(top) = pending.last_mut() {
+            *top -= 1;
+        }
+        match repr {
+            Repr::RPointer(n) if n == 0 || n > cells => return Some((end, format!("shared pointer {} out of range", n))),
+            Repr::RInt(_) | Repr::RPointer(_) | Repr::RCodePointer{..} | Repr::RBlock(_,0) => {}
+            Repr::RBlock(_,len) => {
+                cells += 1;
+                pending.push(len);
+            }
+            _ => cells += 1
+        }
+        while pending.last() == Some(&0) {
+            pending.pop();
+        }
+        if pending.is_empty() {
+            if end < data.len() {
+                return Some((end, "value ends before the data does".to_string()));
+            }
+            if cells as u64 != objects {
+                return Some((data.len(), format!("{} shared objects, header says {}", cells, objects)));
+            }
+            return None;
+        }
+    }
+    Some((data.len(), "data ends before the value does".to_string()))
+}
+
+// One entry per segment whose data doesn't match its digest
+pub fn locate_corruption(contents: &[u8]) -> Vec<Corruption> {
+    let mut result = vec![];
+    for (segment,range) in segment_ranges(contents).into_iter().enumerate() {
+        let data_end = range.end - 16;
+        let (header,data) = match marshal_header(&contents[range.start + 4..data_end]) {
+            Some(h) => h,
+            None => {
+                result.push(Corruption{segment, range, reason:"no valid marshal header".to_string()});
+                continue;
+            }
+        };
+        let data_start = data_end - data.len();
+        if DigestBytes::new(&md5(data)) == DigestBytes::new(&contents[data_end..]) {
+            continue;
+        }
+        if header.kind == HeaderKind::Compressed {
+            result.push(Corruption{segment, range:data_start..range.end, reason:"compressed, so the damage can't be narrowed down".to_string()});
+            continue;
+        }
+        result.push(match first_inconsistency(data, header.objects) {
+            Some((end,reason)) => Corruption{segment, range:data_start..data_start + end, reason},
+            None => Corruption{segment, range:data_start..range.end, reason:"structure intact, so a string or number in the data, or the digest itself, was changed".to_string()}
+        });
+    }
+    result
+}
+
+// One problem found by scan_errors. offset is in the file.
+#[derive(Debug,Clone,PartialEq,Serialize,JsonSchema)]
+pub struct ScanError {
+    pub segment: usize,
+    pub offset: usize,
+    pub message: String
+}
+
+// Decodes every segment as far as it can, carrying on after a bad object from the next byte that
+// decodes, and after a bad segment from the next one, whose position its stop offset gives.
+// Reports at most max_per_segment problems per segment, so that all of a damaged file's problems
+// can be seen at once rather than one per run. Bytes that misdecode after a resync can give
+// spurious errors, so everything after the first in a segment is a lead rather than a certainty.
+pub fn scan_errors(contents: &[u8], max_per_segment: usize) -> Vec<ScanError> {
+    let mut result = vec![];
+    for (segment,range) in segment_ranges(contents).into_iter().enumerate() {
+        let mut errors = vec![];
+        let data_end = range.end - 16;
+        let (header,data) = match marshal_header(&contents[range.start + 4..data_end]) {
+            Some(h) => h,
+            None => {
+                result.push(ScanError{segment, offset:range.start + 4, message:"no valid marshal header".to_string()});
+                continue;
+            }
+        };
+        let data_start = data_end - data.len();
+        if header.kind == HeaderKind::Compressed {
+            continue;
+        }
+        let mut pos = 0;
+        let mut cells = 0u64;
+        let mut resyncing = false;
+        'scan: while pos < data.len() && errors.len() < max_per_segment {
+            let mut iter = ObjectIter::new(&data[pos..]);
+            while let Some(object) = iter.next() {
+                match object {
+                    Ok((offset,repr)) => {
+                        resyncing = false;
+                        match repr {
+                            Repr::RPointer(n) if n == 0 || n as u64 > cells => {
+                                errors.push(ScanError{segment, offset:data_start + pos + offset, message:format!("shared pointer {} out of range, only {} objects so far", n, cells)});
+                            }
+                            Repr::RInt(_) | Repr::RPointer(_) | Repr::RCodePointer{..} | Repr::RBlock(_,0) => {}
+                            _ => cells += 1
+                        }
+                        if errors.len() >= max_per_segment {
+                            break 'scan;
+                        }
+                    }
+                    Err(e) => {
+                        let at = pos + iter.offset();
+                        if !resyncing {
+                            let message = e.stuff.first().map(|(_,m)|m.clone()).unwrap_or_default();
+                            errors.push(ScanError{segment, offset:data_start + at, message});
+                        }
+                        resyncing = true;
+                        pos = at + 1;
+                        continue 'scan;
+                    }
+                }
+            }
+            break;
+        }
+        if errors.len() < max_per_segment && cells != header.objects {
+            errors.push(ScanError{segment, offset:data_start, message:format!("{} shared objects, header says {}", cells, header.objects)});
+        }
+        if errors.len() < max_per_segment && DigestBytes::new(&md5(data)) != DigestBytes::new(&contents[data_end..]) {
+            errors.push(ScanError{segment, offset:data_end, message:"digest mismatch".to_string()});
+        }
+        result.extend(errors);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn intact_file() {
+        assert!(locate_corruption(include_bytes!("selftest.vo")).is_empty());
+    }
+
+    #[test]
+    fn corrupted_structure_and_contents() {
+        let sample = include_bytes!("selftest.vo");
+        // first object of segment 0 is at 28; 31 is inside the string "Selftest"
+        let mut damaged = sample.to_vec();
+        damaged[31] ^= 0x20;
+        let found = locate_corruption(&damaged);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].range, 28..0x88 + 16);
+
+        let mut damaged = sample.to_vec();
+        damaged[29] = 0x11;
+        let found = locate_corruption(&damaged);
+        assert_eq!(found[0].segment, 0);
+        assert_eq!(found[0].range, 28..30);
+        assert_eq!(found[0].reason, "undecodable object");
+    }
+
+    #[test]
+    fn several_errors_per_segment() {
+        use crate::testing::{block,int,vo_file};
+        let segment = block(0).fields(vec![int(1), int(2), int(3), int(4)]);
+        let mut contents = vo_file(8991, &[segment.clone(), segment]);
+        assert!(scan_errors(&contents, 10).is_empty());
+        // segment 0's data is at 28..33 and segment 1's at 73..78: damage two ints in the first,
+        // and turn two ints of the second into a pointer to nowhere
+        contents[29] = 0x14;
+        contents[31] = 0x15;
+        contents[74] = 0x04;
+        contents[75] = 0x05;
+        let found = scan_errors(&contents, 10);
+        let messages: Vec<(usize,usize,&str)> = found.iter().map(|e|(e.segment, e.offset, e.message.as_str())).collect();
+        assert_eq!(messages, vec![
+            (0, 29, "Unhandled code: 14"),
+            (0, 31, "Unhandled code: 15"),
+            (0, 33, "digest mismatch"),
+            (1, 74, "shared pointer 5 out of range, only 1 objects so far"),
+            (1, 78, "digest mismatch")
+        ]);
+        // the cap is per segment
+        assert_eq!(scan_errors(&contents, 1).len(), 2);
+    }
+}
@@ -0,0 +1,81 @@
+// Log output. Human-readable logs to stderr go through stderrlog as they always have; a log file
+// or JSON lines go through Logger, which writes one line per message.
+
+use std::fs::File;
+use std::io::{self,stderr,Write};
+use std::sync::Mutex;
+
+use chrono::Local;
+use log::{Level,LevelFilter,Log,Metadata,Record};
+
+use crate::commands::{GlobalOpts,LogFormat};
+
+struct Logger {
+    level: LevelFilter,
+    format: LogFormat,
+    out: Mutex<Box<dyn Write+Send>>
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level && metadata.target().starts_with("mathparse")
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let timestamp = Local::now().format("%Y-%m-%dT%H:%M:%S%.3f%:z");
+        let line = match self.format {
+            LogFormat::Human => format!("{} - {} - {}", timestamp, record.level(), record.args()),
+            LogFormat::Json => serde_json::json!({
+                "timestamp": timestamp.to_string(),
+                "level": record.level().to_string(),
+                "target": record.target(),
+                "message": record.args().to_string()
+            }).to_string()
+        };
+        let mut out = self.out.lock().unwrap();
+        // nowhere left to report a failure to write a log message
+        let _ = writeln!(out, "{}", line);
+    }
+
+    fn flush(&self) {
+        let _ = self.out.lock().unwrap().flush();
+    }
+}
+
+// Same levels as stderrlog: errors only by default, then one more level per -v
+fn level(global: &GlobalOpts) -> LevelFilter {
+    if global.quiet {
+        return LevelFilter::Off;
+    }
+    match global.verbosity {
+        0 => Level::Error,
+        1 => Level::Warn,
+        2 => Level::Info,
+        3 => Level::Debug,
+        _ => Level::Trace
+    }.to_level_filter()
+}
+
+pub fn init(global: &GlobalOpts) -> io::Result<()> {
+    if global.log_file.is_none() && global.log_format == LogFormat::Human {
+        stderrlog::new()
+            .module("mathparse")
+            .quiet(global.quiet)
+            .verbosity(global.verbosity as usize)
+            .timestamp(stderrlog::Timestamp::Millisecond)
+            .init()
+            .unwrap();
+        return Ok(());
+    }
+    let out: Box<dyn Write+Send> = match &global.log_file {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(stderr())
+    };
+    let level = level(global);
+    log::set_boxed_logger(Box::new(Logger{level, format:global.log_format, out:Mutex::new(out)})).unwrap();
+    log::set_max_level(level);
+    Ok(())
+}
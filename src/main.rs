@@ -1,48 +1,94 @@
 #[macro_use]
-extern crate clap;
-#[macro_use]
 extern crate log;
 
-mod parse;
-mod types;
+mod commands;
+mod logging;
 
-use std::fs::read;
+use std::process::ExitCode;
 
-use crate::parse::{file,E};
+use clap::{Parser,Subcommand};
 
-fn print_error(contents: &[u8], error: E) {
-    for (pos, msg) in error.stuff {
-        println!("Error {}", msg);
-        let i = &contents[contents.len() - pos..];
-        for byte in &i[..i.len().min(256)] {
-            print!("{:02x} ", byte);
-        }
-        println!();
-    }
+use crate::commands::GlobalOpts;
+
+#[derive(Debug,Parser)]
+#[command(name = "mathparse", version, about = "Reads compiled Coq libraries (.vo files)")]
+#[command(subcommand_negates_reqs = true)]
+struct Cli {
+    #[command(flatten)]
+    global: GlobalOpts,
+    #[command(flatten)]
+    check: commands::check::CheckArgs,
+    #[command(subcommand)]
+    command: Option<Command>
+}
+
+#[derive(Debug,Subcommand)]
+enum Command {
+    /// Writes a JSON index of all .vo files in a directory tree
+    Index(commands::index::IndexArgs),
+    /// Finds the library that defines a qualified name, using an index from 'mathparse index'
+    Which(commands::which::WhichArgs),
+    /// Lists .vo files that are older than their .v source or were built against different dependencies
+    Stale(commands::stale::StaleArgs),
+    /// Prints dependency graph metrics for the .vo files in a directory tree
+    Graph(commands::graph::GraphArgs),
+    /// Compares two builds of a development, library by library
+    Diff(commands::diff::DiffArgs),
+    /// Parses every .vo file in a directory and compares the results with recorded expectations
+    Corpus(commands::corpus::CorpusArgs),
+    /// Compiles the fixture sources with each Coq on PATH and records the results as test expectations
+    #[cfg(feature = "fixtures")]
+    Fixtures(commands::fixtures::FixturesArgs),
+    /// Counts the kinds of marshalled objects in each segment of a .vo file
+    Stats(commands::stats::StatsArgs),
+    /// Gathers everything known about why a .vo file fails to parse into one JSON bundle for a bug report
+    Triage(commands::triage::TriageArgs),
+    /// Writes the marshalled data of a segment as JSON, without holding it all in memory
+    Dump(commands::dump::DumpArgs),
+    /// Suggests Rust types for a segment from the shape of its marshalled data
+    InferTypes(commands::infer_types::InferTypesArgs),
+    /// Saves a parsed .vo file as a snapshot that loads faster than reparsing
+    Snapshot(commands::snapshot::SnapshotArgs),
+    /// Loads a snapshot written by 'mathparse snapshot' and prints a summary
+    LoadSnapshot(commands::snapshot::LoadSnapshotArgs),
+    /// Prints the JSON Schema of a subcommand's --format json output
+    Schema(commands::schema::SchemaArgs),
+    /// Parses a small built-in .vo file to check that this binary works
+    Selftest,
+    /// Prints a shell completion script
+    Completions(commands::completions::CompletionsArgs),
+    /// Prints a man page in roff format
+    Manpage
 }
 
-fn main() {
-    let matches = clap_app!(mathparse =>
-        (@arg INPUT: +required "Input .vo file to parse")
-        (@arg quiet: -q "Disables output messages")
-        (@arg verbosity: -v +multiple "Increases message verbosity")
-    ).get_matches();
-    
-    stderrlog::new()
-        .module(module_path!())
-        .quiet(matches.is_present("quiet"))
-        .verbosity(matches.occurrences_of("verbosity") as usize)
-        .timestamp(stderrlog::Timestamp::Millisecond)
-        .init()
-        .unwrap();
-
-    let file_name = matches.value_of("INPUT").unwrap();
-    let file_contents = read(file_name).unwrap();
-
-    match file(&file_contents) {
-        Ok((_,())) => {}
-        Err(nom::Err::Error(e)) => print_error(&file_contents, e),
-        Err(nom::Err::Failure(e)) => print_error(&file_contents, e),
-        Err(e) => panic!("{:?}", e)
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    if let Err(e) = logging::init(&cli.global) {
+        eprintln!("Can't open log file: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    let global = &cli.global;
+    match &cli.command {
+        None => commands::check::run(global, &cli.check),
+        Some(Command::Index(args)) => commands::index::run(global, args),
+        Some(Command::Which(args)) => commands::which::run(global, args),
+        Some(Command::Stale(args)) => commands::stale::run(global, args),
+        Some(Command::Graph(args)) => commands::graph::run(global, args),
+        Some(Command::Diff(args)) => commands::diff::run(global, args),
+        Some(Command::Corpus(args)) => commands::corpus::run(global, args),
+        #[cfg(feature = "fixtures")]
+        Some(Command::Fixtures(args)) => commands::fixtures::run(global, args),
+        Some(Command::Stats(args)) => commands::stats::run(global, args),
+        Some(Command::Triage(args)) => commands::triage::run(global, args),
+        Some(Command::Dump(args)) => commands::dump::run(global, args),
+        Some(Command::InferTypes(args)) => commands::infer_types::run(global, args),
+        Some(Command::Snapshot(args)) => commands::snapshot::run(global, args),
+        Some(Command::LoadSnapshot(args)) => commands::snapshot::run_load(global, args),
+        Some(Command::Schema(args)) => commands::schema::run(global, args),
+        Some(Command::Selftest) => commands::selftest::run(global),
+        Some(Command::Completions(args)) => commands::completions::run(global, args),
+        Some(Command::Manpage) => commands::manpage::run(global)
     }
 }
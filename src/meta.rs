@@ -0,0 +1,56 @@
+use std::time::{SystemTime,UNIX_EPOCH};
+
+use schemars::JsonSchema;
+use serde::{Deserialize,Serialize};
+
+use crate::parse::{supported_magics,ParseOptions};
+
+// What produced a set of results, so that caches can throw away those from other parser versions
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize,JsonSchema)]
+pub struct Meta {
+    pub mathparse_version: String,
+    // .vo magic numbers this version parses, out of all the known ones in parse::VO_MAGICS
+    pub magics: Vec<i32>,
+    pub utf8: String,
+    pub code_pointers: String,
+    // Seconds since the Unix epoch
+    pub created: u64
+}
+
+// A --format json report, with what produced it
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize,JsonSchema)]
+pub struct Report<T> {
+    pub meta: Meta,
+    pub report: T
+}
+
+impl<T> Report<T> {
+    pub fn new(options: &ParseOptions, report: T) -> Self {
+        Report{meta: Meta::new(options), report}
+    }
+}
+
+// Now, or SOURCE_DATE_EPOCH if that's set, so that output can be made reproducible
+fn created() -> u64 {
+    match std::env::var("SOURCE_DATE_EPOCH").ok().and_then(|s|s.parse().ok()) {
+        Some(secs) => secs,
+        None => SystemTime::now().duration_since(UNIX_EPOCH).map(|d|d.as_secs()).unwrap_or(0)
+    }
+}
+
+impl Meta {
+    pub fn new(options: &ParseOptions) -> Self {
+        Meta {
+            mathparse_version: env!("CARGO_PKG_VERSION").to_string(),
+            magics: supported_magics(),
+            utf8: format!("{:?}", options.utf8),
+            code_pointers: format!("{:?}", options.code_pointers),
+            created: created()
+        }
+    }
+    // Whether results with this meta would come out the same if produced now with these options
+    pub fn is_current(&self, options: &ParseOptions) -> bool {
+        let now = Meta::new(options);
+        self.mathparse_version == now.mathparse_version && self.magics == now.magics && self.utf8 == now.utf8 && self.code_pointers == now.code_pointers
+    }
+}
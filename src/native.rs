@@ -0,0 +1,17 @@
+use std::path::{Path,PathBuf};
+
+use crate::types::DirPath;
+
+// coqc -native-compiler puts its plugins in this directory, next to the .vo
+const NATIVE_DIR:&str = ".coq-native";
+
+// e.g. Coq.Init.Datatypes -> NCoq_Init_Datatypes.cmxs
+pub fn native_file_name(name: &DirPath) -> String {
+    let segments: Vec<_> = name.segments().iter().map(|s|s.to_string_lossy()).collect();
+    format!("N{}.cmxs", segments.join("_"))
+}
+
+pub fn native_artifact(vo_path: &Path, name: &DirPath) -> PathBuf {
+    let dir = vo_path.parent().unwrap_or_else(||Path::new(""));
+    dir.join(NATIVE_DIR).join(native_file_name(name))
+}
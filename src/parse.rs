@@ -1,21 +1,34 @@
+// The combinators below all return `impl Fn(&mut Memory, &[u8]) -> IResult<..>`
+#![allow(clippy::type_complexity)]
+
 use std::any::Any;
-use std::mem::swap;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::ops::Range;
 use std::rc::Rc;
+use std::collections::{BTreeMap,BTreeSet,HashMap};
+use std::hash::Hash;
+use std::sync::{Arc,RwLock};
+use std::sync::atomic::{AtomicBool,Ordering};
 
 use md5::{Md5,Digest};
 use nom::IResult;
 use nom::bytes::complete::{tag,take,take_till};
-use nom::combinator::all_consuming;
 use nom::error::{ErrorKind,ParseError};
-use nom::number::complete::{be_i8,be_i16,be_i32,be_i64,be_u8,be_u16,be_u24,be_u32,be_u64};
+use nom::number::complete::{be_f64,be_i8,be_i16,be_i32,be_i64,be_u8,be_u16,be_u24,be_u32,be_u64,le_f64};
+use serde::{Deserialize,Serialize};
 
-use crate::types::DigestBytes;
+use crate::types::{BigInt,DigestBytes,Uint63};
 use crate::types::SummaryDisk;
 
-const VO_MAGIC:i32 = 8991;
+pub mod generic;
+
+const MARSHAL_MAGIC_SMALL:u32 = 0x8495A6BE;
+const MARSHAL_MAGIC_BIG:u32 = 0x8495A6BF;
+const MARSHAL_MAGIC_COMPRESSED:u32 = 0x8495A6BD;
+// magic number, then one segment's stop offset, marshal header and digest
+const MIN_VO_LEN:usize = 4 + 4 + 20 + 16;
 
-#[allow(non_camel_case_types)]
-type u63 = u64;
 
 #[derive(Debug)]
 pub struct E {
@@ -48,7 +61,7 @@ impl<'a> ParseError<&'a[u8]> for E {
     }
 }
 
-pub fn fail<'a,T>(input: &'a[u8], msg: String) -> IResult<&'a[u8],T,E> {
+pub fn fail<T>(input: &[u8], msg: String) -> IResult<&[u8],T,E> {
     Err(nom::Err::Failure(E::new(input,msg)))
 }
 
@@ -64,14 +77,27 @@ pub trait VoParseRef where Self:Sized+Clone {
 
 //////////////////////////////////////////////////////
 
+// One object of marshalled data as parse_object decodes it, before any typed parser sees it.
+// A block is only its header; its fields are the objects that follow it.
 #[derive(Debug,Clone)]
-enum Repr {
+pub enum Repr {
+    // An immediate int, including constant constructors
     RInt(i64),
-    RInt63(u63),
+    // Custom blocks with built-in decoders, by identifier: _j, _i, _l, _n and _z
+    RInt63(Uint63),
+    RInt32(i32),
+    RInt64(i64),
+    RNativeInt(i64),
+    RBigInt(BigInt),
+    // A custom block decoded by a parser from register_custom: its identifier and payload bytes
+    RCustom(String,Vec<u8>),
+    // The tag and number of fields of a block. A block of 0 fields is an atom, not a shared object.
     RBlock(u8,usize),
     RString(Vec<u8>),
+    RDouble(f64),
+    // A pointer to the shared object this many objects back, counting from 1
     RPointer(usize),
-    RCode(i64)
+    RCodePointer{offset: u32, digest: DigestBytes}
 }
 
 const CODE_INT8:u8 = 0;
@@ -94,6 +120,42 @@ const CODE_CODEPOINTER:u8 = 16;
 const CODE_INFIXPOINTER:u8 = 17;
 const CODE_CUSTOM:u8 = 18;
 const CODE_BLOCK64:u8 = 19;
+// Custom blocks as OCaml 4.08 and later write them: with their sizes, or for types of a fixed size,
+// without
+const CODE_CUSTOM_LEN:u8 = 24;
+const CODE_CUSTOM_FIXED:u8 = 25;
+
+// The name of the marshal code that starts with this byte, for reports
+pub fn code_name(byte: u8) -> &'static str {
+    match byte {
+        0x80..=0xff => "SMALL_BLOCK",
+        0x40..=0x7f => "SMALL_INT",
+        0x20..=0x3f => "SMALL_STRING",
+        CODE_INT8 => "INT8",
+        CODE_INT16 => "INT16",
+        CODE_INT32 => "INT32",
+        CODE_INT64 => "INT64",
+        CODE_SHARED8 => "SHARED8",
+        CODE_SHARED16 => "SHARED16",
+        CODE_SHARED32 => "SHARED32",
+        CODE_DOUBLE_ARRAY32_LITTLE => "DOUBLE_ARRAY32_LITTLE",
+        CODE_BLOCK32 => "BLOCK32",
+        CODE_STRING8 => "STRING8",
+        CODE_STRING32 => "STRING32",
+        CODE_DOUBLE_BIG => "DOUBLE_BIG",
+        CODE_DOUBLE_LITTLE => "DOUBLE_LITTLE",
+        CODE_DOUBLE_ARRAY8_BIG => "DOUBLE_ARRAY8_BIG",
+        CODE_DOUBLE_ARRAY8_LITTLE => "DOUBLE_ARRAY8_LITTLE",
+        CODE_DOUBLE_ARRAY32_BIG => "DOUBLE_ARRAY32_BIG",
+        CODE_CODEPOINTER => "CODEPOINTER",
+        CODE_INFIXPOINTER => "INFIXPOINTER",
+        CODE_CUSTOM => "CUSTOM",
+        CODE_BLOCK64 => "BLOCK64",
+        CODE_CUSTOM_LEN => "CUSTOM_LEN",
+        CODE_CUSTOM_FIXED => "CUSTOM_FIXED",
+        20..=23 | 26..=31 => "UNKNOWN"
+    }
+}
 
 #[derive(Debug,Clone)]
 pub enum Data {
@@ -102,8 +164,77 @@ pub enum Data {
     Atm(u8)
 }
 
+// What to do with identifiers that aren't valid UTF-8
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Utf8Policy {
+    Error,
+    Warn,
+    Preserve
+}
+
+// What the typed combinators do on meeting a code pointer, i.e. part of a marshalled function
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum CodePointerPolicy {
+    Error,
+    // Read it as unit, as if the field held ()
+    Skip,
+    // Pass it on, for the code_pointer combinator to return
+    Opaque
+}
+
+// Shared flag for stopping a parse from another thread. The parser checks it between segments and
+// between the elements of each array, and fails with "Parse cancelled" once it is set.
+#[derive(Debug,Clone,Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed)
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct ParseOptions {
+    pub utf8: Utf8Policy,
+    pub cancel: Option<CancelToken>,
+    pub code_pointers: CodePointerPolicy,
+    // Fill in VoFile::offsets
+    pub record_offsets: bool,
+    // Leave any bytes after the last segment as the remaining input, instead of failing
+    pub allow_trailing: bool
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions{utf8: Utf8Policy::Error, cancel: None, code_pointers: CodePointerPolicy::Error, record_offsets: false, allow_trailing: false}
+    }
+}
+
+impl ParseOptions {
+    fn check_cancelled<'b>(&self, i: &'b[u8]) -> IResult<&'b[u8],(),E> {
+        match &self.cancel {
+            Some(token) if token.is_cancelled() => fail(i, "Parse cancelled".to_string()),
+            _ => Ok((i,()))
+        }
+    }
+}
+
 pub struct Memory {
-    cells: Vec<Option<Rc<dyn Any>>>
+    // Each shared object with the name of its type, or None for a block still being read
+    cells: Vec<Option<(Rc<dyn Any>,&'static str)>>,
+    options: ParseOptions,
+    // Number of shared objects the marshal header promises, if known
+    expected: Option<usize>,
+    // Heap words the header declares for 64-bit OCaml, if it records them
+    words: Option<usize>,
+    // The struct and field that derived parsers are reading, for error messages
+    field: Option<(&'static str,&'static str)>
 }
 
 pub struct SemanticError {
@@ -112,34 +243,88 @@ pub struct SemanticError {
 
 impl SemanticError {
     pub fn new(msg:String) -> Self {
-        SemanticError{msg:msg}
+        SemanticError{msg}
     }
     pub fn msg<T>(msg:String) -> Result<T,Self> {
         Err(SemanticError::new(msg))
     }
-    fn to_nom(self, i:&[u8]) -> nom::Err<E> {
+    fn into_nom(self, i:&[u8]) -> nom::Err<E> {
         nom::Err::Failure(E{stuff:vec![(i.len(), self.msg)]})
     }
 }
 
+// A type name without module paths: alloc::rc::Rc<mathparse::types::Ident> is Rc<Ident>
+fn short_type_name(name: &str) -> String {
+    let mut result = String::new();
+    let mut word = String::new();
+    for c in name.chars().chain(std::iter::once(' ')) {
+        if c.is_alphanumeric() || c == '_' || c == ':' {
+            word.push(c);
+        } else {
+            result.push_str(word.rsplit("::").next().unwrap_or(""));
+            word.clear();
+            result.push(c);
+        }
+    }
+    result.pop();
+    result
+}
+
 impl Memory {
-    fn with_capacity(size: usize) -> Self {
-        Memory{cells: Vec::with_capacity(size)}
+    fn with_capacity(size: usize, options: &ParseOptions) -> Self {
+        Memory{cells: Vec::with_capacity(size), options: options.clone(), expected: None, words: None, field: None}
+    }
+    // The next object, with the code pointer policy applied. Fails as soon as more shared objects
+    // have been allocated than the header allows, which usually means a parser that shares too much.
+    fn read_object<'b>(&self, i: &'b[u8]) -> IResult<&'b[u8],Repr,E> {
+        if let Some(expected) = self.expected {
+            if self.cells.len() > expected {
+                return fail(i, format!("Object count mismatch: header says {} shared objects, parsing has already allocated {}", expected, self.cells.len()));
+            }
+        }
+        let (newi,r) = parse_object(i)?;
+        match (&r, self.options.code_pointers) {
+            (Repr::RCodePointer{..}, CodePointerPolicy::Error) => fail(i, "Code pointer found, but marshalled functions aren't supported".to_string()),
+            (Repr::RCodePointer{..}, CodePointerPolicy::Skip) => Ok((newi,Repr::RInt(0))),
+            _ => Ok((newi,r))
+        }
+    }
+    pub fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+    // Called by #[derive(VoParse)] before each field; returns the context it replaces
+    pub fn enter_field(&mut self, name: &'static str, field: &'static str) -> Option<(&'static str,&'static str)> {
+        self.field.replace((name, field))
+    }
+    // Called by #[derive(VoParse)] after the last field, with what the first enter_field returned
+    pub fn leave_fields(&mut self, saved: Option<(&'static str,&'static str)>) {
+        self.field = saved;
+    }
+    // " at Struct.field" for the field being read, if any
+    pub fn field_context(&self) -> String {
+        self.field.map(|(name,field)|format!(" at {}.{}", name, field)).unwrap_or_default()
     }
     fn len(&self) -> usize {
         self.cells.len()
     }
     fn push<T:'static>(&mut self, rc: Rc<T>) {
-        self.cells.push(Some(rc))
+        self.cells.push(Some((rc, std::any::type_name::<T>())))
     }
     fn point_back2<T:'static>(&mut self, offset: usize) -> Result<Rc<T>,SemanticError> {
-        let index = self.cells.len() - offset;
+        let declared = self.expected.map(|n|format!(" (the header declares {})", n)).unwrap_or_default();
+        let index = match self.cells.len().checked_sub(offset) {
+            Some(index) => index,
+            None => return SemanticError::msg(format!("Pointer {} is to before the start of the segment, only {} objects so far{}", offset, self.cells.len(), declared))
+        };
         if index >= self.cells.len() {
-            return SemanticError::msg(format!("Pointer is to next object, is this allowed?"));
+            return SemanticError::msg(format!("Pointer 0 would be to object {}, which hasn't been read yet{}", index, declared));
         }
         match &self.cells[index] {
-            Some(rc) => rc.clone().downcast().map_err(|rc|SemanticError::new(format!("downcasting error on pointer"))),
-            _ => SemanticError::msg(format!("Pointer is to object that we haven't finished building, is this allowed?"))
+            Some((rc,found)) => rc.clone().downcast().map_err(|_|SemanticError::new(format!("Pointer {} is to object {}, which was read as {} but is wanted here as {}",
+                offset, index, short_type_name(found), short_type_name(std::any::type_name::<T>())))),
+            // OCaml allows this: the block exists as soon as its header is read. But a value
+            // containing itself can't be built from Rcs, so typed parsing stops here.
+            _ => SemanticError::msg(format!("Pointer {} is to cell {}, a block that contains it: the value is cyclic, which typed parsing doesn't support (parse::generic does)", offset, index))
         }
     }
     fn reserve_for_struct(&mut self) -> usize {
@@ -150,7 +335,7 @@ impl Memory {
         match self.cells[addr] {
             None => {
                 let rc = Rc::new(data);
-                self.cells[addr] = Some(rc.clone());
+                self.cells[addr] = Some((rc.clone(), std::any::type_name::<T>()));
                 rc
             }
             _ => panic!("backfill_struct: expecting cell to be under construction")
@@ -160,22 +345,212 @@ impl Memory {
 
 //////////////////////////////////////////////////////
 
-fn vo_magic(i: &[u8]) -> IResult<&[u8],(),E> {
-    let (i, magic) = be_i32(i)?;
-    if magic == VO_MAGIC {
-        Ok((i,()))
-    } else {
-        fail(i,format!("vo_magic {}", VO_MAGIC))
+// How a .vo file is divided into segments
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Framing {
+    // Each segment preceded by the offset where it stops and followed by its digest
+    StopOffsets,
+    // Named segments listed in a table (Coq's ObjFile), from 8.13 on. Not parsed yet.
+    SegmentTable
+}
+
+#[derive(Debug,PartialEq,Eq)]
+pub struct Layout {
+    pub framing: Framing,
+    // What each segment holds, in file order
+    pub segments: &'static [&'static str]
+}
+
+static STOP_OFFSETS: Layout = Layout{framing: Framing::StopOffsets, segments: &["summary", "library", "universes", "tasks", "opaques"]};
+static SEGMENT_TABLE: Layout = Layout{framing: Framing::SegmentTable, segments: &["summary", "library", "universes", "tasks", "opaques"]};
+
+// The magic number at the start of a .vo file, the Coq release that writes it, its layout, and
+// whether files from that release are known to parse. 8.10 to 8.12 also use stop offsets, but no
+// fixture shows that their summaries match 8.9's.
+pub const VO_MAGICS: &[(i32,&str,&Layout,bool)] = &[
+    (8991, "8.9", &STOP_OFFSETS, true),
+    (81000, "8.10", &STOP_OFFSETS, false),
+    (81100, "8.11", &STOP_OFFSETS, false),
+    (81200, "8.12", &STOP_OFFSETS, false),
+    (81300, "8.13", &SEGMENT_TABLE, false),
+    (81400, "8.14", &SEGMENT_TABLE, false),
+    (81500, "8.15", &SEGMENT_TABLE, false),
+    (81600, "8.16", &SEGMENT_TABLE, false),
+    (81700, "8.17", &SEGMENT_TABLE, false),
+    (81800, "8.18", &SEGMENT_TABLE, false),
+    (81900, "8.19", &SEGMENT_TABLE, false),
+    (82000, "8.20", &SEGMENT_TABLE, false),
+    (90000, "9.0", &SEGMENT_TABLE, false)
+];
+
+// Coq release that writes .vo files with the given magic number
+pub fn coq_version(magic: i32) -> Option<&'static str> {
+    VO_MAGICS.iter().find(|(m,_,_,_)|*m == magic).map(|(_,v,_,_)|*v)
+}
+
+pub fn layout(magic: i32) -> Option<&'static Layout> {
+    VO_MAGICS.iter().find(|(m,_,_,_)|*m == magic).map(|(_,_,l,_)|*l)
+}
+
+// Magic numbers of the releases whose files this version parses
+pub fn supported_magics() -> Vec<i32> {
+    VO_MAGICS.iter().filter(|(_,_,_,ok)|*ok).map(|(m,_,_,_)|*m).collect()
+}
+
+// The magic number and the layout it selects, for the releases whose files can be parsed
+fn vo_magic(input: &[u8]) -> IResult<&[u8],(i32,&'static Layout),E> {
+    let (i, magic) = be_i32(input)?;
+    match VO_MAGICS.iter().find(|(m,_,_,_)|*m == magic) {
+        Some((_,version,layout,supported)) => match layout.framing {
+            Framing::StopOffsets if *supported => Ok((i,(magic,layout))),
+            Framing::StopOffsets => fail(input,format!("Coq {} .vo files aren't supported yet", version)),
+            Framing::SegmentTable => fail(input,format!("Coq {} .vo files aren't supported yet: they are divided by a segment table (Coq's ObjFile), which isn't parsed", version))
+        },
+        None => fail(input,format!("Unknown .vo magic number {}", magic))
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum HeaderKind {
+    // 20 bytes of 32-bit fields, as written by most runtimes
+    Small,
+    // 32 bytes of 64-bit fields, for data too large for the small header
+    Big,
+    // OCaml 5.1+: variable-length fields, then zstd-compressed data
+    Compressed
+}
+
+// A marshal header of any kind. size32 is 0 in big headers, which don't record it.
+// data_len is what follows the header in the file, uncompressed_len what it decompresses to.
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct Header {
+    pub kind: HeaderKind,
+    pub header_len: usize,
+    pub data_len: u64,
+    pub uncompressed_len: u64,
+    pub objects: u64,
+    pub size32: u64,
+    pub size64: u64
+}
+
+impl Header {
+    // Checks that the counts agree with each other: every shared object takes at least a byte of
+    // data and a word of heap, and only compressed data can change length. Heap sizes of 0 are
+    // taken as not recorded, as in hand-written files.
+    pub fn check(&self) -> Result<(),String> {
+        if self.kind != HeaderKind::Compressed && self.data_len != self.uncompressed_len {
+            return Err(format!("Uncompressed marshal header has data length {} but uncompressed length {}", self.data_len, self.uncompressed_len));
+        }
+        if self.objects > self.uncompressed_len {
+            return Err(format!("Marshal header says {} shared objects in only {} bytes", self.objects, self.uncompressed_len));
+        }
+        if (self.size64 != 0 && self.size64 < self.objects) || (self.size32 != 0 && self.size32 < self.objects) {
+            return Err(format!("Marshal header says {} shared objects, but only {} words (32-bit) and {} words (64-bit) of heap", self.objects, self.size32, self.size64));
+        }
+        Ok(())
     }
 }
 
-fn header(i: &[u8]) -> IResult<&[u8],(i32,i32,i32,i32),E> {
-    let (i,_) = tag(&[132,149,166,190])(i)?;  // magic
-    let (i,length) = be_i32(i)?;
-    let (i,objects) = be_i32(i)?;
-    let (i,size32) = be_i32(i)?;
-    let (i,size64) = be_i32(i)?;
-    Ok((i,(length,size32,size64,objects)))
+// Big-endian base 128, high bit set on all but the last byte
+fn vlq(i: &[u8]) -> IResult<&[u8],u64,E> {
+    let mut n = 0u64;
+    let mut i = i;
+    loop {
+        let (rest,c) = be_u8(i)?;
+        if n >> 57 != 0 {
+            return fail(i, "Variable-length integer too large".to_string());
+        }
+        n = (n << 7) | (c & 0x7f) as u64;
+        i = rest;
+        if c & 0x80 == 0 {
+            return Ok((i,n));
+        }
+    }
+}
+
+fn header(input: &[u8]) -> IResult<&[u8],Header,E> {
+    let (i,magic) = be_u32(input)?;
+    match magic {
+        MARSHAL_MAGIC_SMALL => {
+            let (i,data_len) = be_u32(i)?;
+            let (i,objects) = be_u32(i)?;
+            let (i,size32) = be_u32(i)?;
+            let (i,size64) = be_u32(i)?;
+            let data_len = data_len as u64;
+            Ok((i,Header{kind:HeaderKind::Small, header_len:20, data_len, uncompressed_len:data_len, objects:objects as u64, size32:size32 as u64, size64:size64 as u64}))
+        }
+        MARSHAL_MAGIC_BIG => {
+            let (i,_reserved) = be_u32(i)?;
+            let (i,data_len) = be_u64(i)?;
+            let (i,objects) = be_u64(i)?;
+            let (i,size64) = be_u64(i)?;
+            Ok((i,Header{kind:HeaderKind::Big, header_len:32, data_len, uncompressed_len:data_len, objects, size32:0, size64}))
+        }
+        MARSHAL_MAGIC_COMPRESSED => {
+            let (i,header_len) = be_u8(i)?;
+            let header_len = (header_len & 0x3f) as usize;
+            let (i,data_len) = vlq(i)?;
+            let (i,uncompressed_len) = vlq(i)?;
+            let (i,objects) = vlq(i)?;
+            let (i,size32) = vlq(i)?;
+            let (i,size64) = vlq(i)?;
+            let read = input.len() - i.len();
+            if header_len < read {
+                return fail(i, format!("Compressed header says it is {} bytes, but its fields take {}", header_len, read));
+            }
+            let (i,_) = take(header_len - read)(i)?;
+            Ok((i,Header{kind:HeaderKind::Compressed, header_len, data_len, uncompressed_len, objects, size32, size64}))
+        }
+        _ => fail(input, format!("Unknown marshal magic number {:#x}", magic))
+    }
+}
+
+#[cfg(feature = "zstd")]
+fn decompress_zstd(data: &[u8]) -> Result<Vec<u8>,String> {
+    zstd::stream::decode_all(data).map_err(|e|format!("Could not decompress marshal data: {}", e))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress_zstd(_data: &[u8]) -> Result<Vec<u8>,String> {
+    Err("Marshal data is compressed; mathparse needs to be built with the zstd feature to read it".to_string())
+}
+
+// The marshalled data that follows a header, decompressed if need be
+fn decompress<'b>(h: &Header, data: &'b[u8]) -> Result<Cow<'b,[u8]>,String> {
+    if h.kind != HeaderKind::Compressed {
+        return Ok(Cow::Borrowed(data));
+    }
+    let result = decompress_zstd(data)?;
+    if result.len() as u64 != h.uncompressed_len {
+        return Err(format!("Decompressed to {} bytes, header says {}", result.len(), h.uncompressed_len));
+    }
+    Ok(Cow::Owned(result))
+}
+
+// The header of a standalone marshal (as from split_segments) and the raw bytes after it
+pub fn marshal_header(marshal: &[u8]) -> Option<(Header,&[u8])> {
+    header(marshal).ok().map(|(data,h)|(h,data))
+}
+
+// Like marshal_header, but with the data decompressed if it was compressed. None if it can't be.
+pub fn marshal_data(marshal: &[u8]) -> Option<(Header,Cow<'_,[u8]>)> {
+    let (h,data) = marshal_header(marshal)?;
+    decompress(&h, data).ok().map(|data|(h,data))
+}
+
+// Standalone marshalled values one after another, as output_value writes them to a channel. Stops
+// at the first thing that isn't a complete one, which is returned with the documents found before it.
+pub fn marshal_documents(mut input: &[u8]) -> (Vec<(Header,&[u8])>,&[u8]) {
+    let mut documents = vec![];
+    while let Ok((i,h)) = header(input) {
+        let len = h.data_len as usize;
+        if i.len() < len {
+            break;
+        }
+        documents.push((h, &i[..len]));
+        input = &i[len..];
+    }
+    (documents,input)
 }
 
 fn header32(i: &[u8]) -> IResult<&[u8],(u8,usize),E> {
@@ -193,19 +568,19 @@ fn header64(i: &[u8]) -> IResult<&[u8],(u8,usize),E> {
 
 fn cstring(i: &[u8]) -> IResult<&[u8],&[u8],E> {
     let (i,string) = take_till(|b|b==0)(i)?;
-    Ok((&i[1..],string))
+    let (i,_) = tag(&[0])(i)?;
+    Ok((i,string))
 }
 
-fn be_u63(i: &[u8]) -> IResult<&[u8], u63, E> {
+fn be_u63(i: &[u8]) -> IResult<&[u8], Uint63, E> {
     let (i,n) = be_i64(i)?;
-    if n < 0 {
-        fail(i, format!("uint63 out of range: {}", n))
-    } else {
-        Ok((i,n as u63))
-    }
+    Ok((i,Uint63::wrap(n as u64)))
 }
 
-fn parse_object(i: &[u8]) -> IResult<&[u8],Repr,E> {
+// Decodes the next object of some marshalled data (after the header) and returns the bytes after
+// it. Code pointers are returned as they are, whatever ParseOptions say; ObjectIter walks all the
+// objects in turn.
+pub fn parse_object(i: &[u8]) -> IResult<&[u8],Repr,E> {
     let (i,data) = be_u8(i)?;
     match data {
         (0x80..=0xff) => {
@@ -265,46 +640,179 @@ fn parse_object(i: &[u8]) -> IResult<&[u8],Repr,E> {
             Ok((i,Repr::RString(string.to_vec())))
         }
         CODE_CODEPOINTER => {
-            let (i,addr) = be_u32(i)?;
-            let (i,_) = take(16usize)(i)?;
-            Ok((i,Repr::RCode(addr as i64)))
+            let (i,offset) = be_u32(i)?;
+            let (i,digest) = take(16usize)(i)?;
+            Ok((i,Repr::RCodePointer{offset, digest:DigestBytes::new(digest)}))
         }
         CODE_CUSTOM => {
-            let (i,string) = cstring(i)?;
-            match string {
-                b"_j" => {
-                    let (i,n) = be_u63(i)?;
-                    Ok((i,Repr::RInt63(n)))
-                }
-                _ => fail(i, format!("Unhandled custom code: {:?}", std::str::from_utf8(string)))
+            let (i,ident) = cstring(i)?;
+            custom_payload(i, ident)
+        }
+        CODE_CUSTOM_LEN => {
+            // the size in memory with 32-bit then 64-bit OCaml, which the payload must account for
+            let (i,ident) = cstring(i)?;
+            let (i,_size32) = be_u32(i)?;
+            let (i,size64) = be_u64(i)?;
+            match fixed_size64(ident) {
+                Some(size) if size != size64 => fail(i, format!("Custom block {} records size {}, but its size is {}", String::from_utf8_lossy(ident), size64, size)),
+                _ => custom_payload(i, ident)
+            }
+        }
+        CODE_CUSTOM_FIXED => {
+            let (i,ident) = cstring(i)?;
+            if ident == b"_z" {
+                return fail(i, "Custom block _z written as fixed-size, but its size varies".to_string());
             }
+            custom_payload(i, ident)
+        }
+        CODE_DOUBLE_BIG => {
+            let (i,x) = be_f64(i)?;
+            Ok((i,Repr::RDouble(x)))
+        }
+        CODE_DOUBLE_LITTLE => {
+            let (i,x) = le_f64(i)?;
+            Ok((i,Repr::RDouble(x)))
         }
         CODE_DOUBLE_ARRAY32_LITTLE|
-            CODE_DOUBLE_BIG|
-            CODE_DOUBLE_LITTLE|
             CODE_DOUBLE_ARRAY8_BIG|
             CODE_DOUBLE_ARRAY8_LITTLE|
             CODE_DOUBLE_ARRAY32_BIG|
             CODE_INFIXPOINTER|
-            20..=31 =>
+            20..=23 | 26..=31 =>
         {
             fail(i, format!("Unhandled code: {:02x}", data))
         }
     }
 }
 
+// The size in memory, with 64-bit OCaml, of a built-in custom block whose size doesn't vary
+fn fixed_size64(ident: &[u8]) -> Option<u64> {
+    match ident {
+        b"_i" => Some(4),
+        b"_j" | b"_l" | b"_n" => Some(8),
+        _ => None
+    }
+}
+
+// A custom block's payload, once its identifier has been read
+fn custom_payload<'b>(i: &'b[u8], ident: &[u8]) -> IResult<&'b[u8],Repr,E> {
+    match ident {
+        b"_j" => {
+            let (i,n) = be_u63(i)?;
+            Ok((i,Repr::RInt63(n)))
+        }
+        b"_i" => {
+            let (i,n) = be_i32(i)?;
+            Ok((i,Repr::RInt32(n)))
+        }
+        b"_l" => {
+            let (i,n) = be_i64(i)?;
+            Ok((i,Repr::RInt64(n)))
+        }
+        b"_n" => {
+            // written with whichever width the writer's nativeint had
+            let (i,width) = be_u8(i)?;
+            match width {
+                1 => {
+                    let (i,n) = be_i32(i)?;
+                    Ok((i,Repr::RNativeInt(n as i64)))
+                }
+                2 => {
+                    let (i,n) = be_i64(i)?;
+                    Ok((i,Repr::RNativeInt(n)))
+                }
+                _ => fail(i, format!("Bad nativeint width: {}", width))
+            }
+        }
+        b"_z" => {
+            // Zarith: sign byte, byte count, then the magnitude least significant byte first
+            let (i,sign) = be_u8(i)?;
+            let (i,len) = be_u32(i)?;
+            let (i,magnitude) = take(len)(i)?;
+            Ok((i,Repr::RBigInt(BigInt{negative: sign != 0, magnitude: magnitude.to_vec()})))
+        }
+        _ => {
+            let ident = String::from_utf8_lossy(ident).into_owned();
+            match custom_parser(&ident) {
+                Some(parser) => {
+                    let (i,payload) = parser(i)?;
+                    Ok((i,Repr::RCustom(ident,payload.to_vec())))
+                }
+                None => fail(i, format!("Unhandled custom code: {:?}", ident))
+            }
+        }
+    }
+}
+
+// Finds the end of a custom block's payload, returning the rest of the input and the payload
+pub type CustomParser = fn(&[u8]) -> IResult<&[u8],&[u8],E>;
+
+static CUSTOM_PARSERS: RwLock<BTreeMap<String,CustomParser>> = RwLock::new(BTreeMap::new());
+
+// Teaches parse_object (and so every combinator and ObjectIter) about another custom block identifier,
+// process-wide. The built-in identifiers _j, _i, _l, _n and _z can't be overridden.
+pub fn register_custom(ident: &str, parser: CustomParser) {
+    CUSTOM_PARSERS.write().unwrap().insert(ident.to_string(), parser);
+}
+
+fn custom_parser(ident: &str) -> Option<CustomParser> {
+    CUSTOM_PARSERS.read().unwrap().get(ident).copied()
+}
+
+// Decodes the objects of some marshalled data one at a time, without building anything.
+// Offsets are relative to the start of the data; iteration stops after the first error.
+pub struct ObjectIter<'b> {
+    data: &'b[u8],
+    rest: &'b[u8],
+    failed: bool
+}
+
+impl<'b> ObjectIter<'b> {
+    pub fn new(data: &'b[u8]) -> Self {
+        ObjectIter{data, rest: data, failed: false}
+    }
+    // Where the next object starts, or after an error, where the object that failed starts
+    pub fn offset(&self) -> usize {
+        self.data.len() - self.rest.len()
+    }
+}
+
+impl<'b> Iterator for ObjectIter<'b> {
+    type Item = Result<(usize,Repr),E>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.rest.is_empty() {
+            return None;
+        }
+        let offset = self.data.len() - self.rest.len();
+        match parse_object(self.rest) {
+            Ok((i,r)) => {
+                self.rest = i;
+                Some(Ok((offset,r)))
+            }
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+            Err(nom::Err::Incomplete(_)) => {
+                self.failed = true;
+                Some(Err(E::new(self.rest, "Incomplete object".to_string())))
+            }
+        }
+    }
+}
+
 pub fn string<'b,F,T:'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<T>,E>
     where F:Fn(Vec<u8>) -> Result<T,SemanticError>
 {
     move|memory,i| {
-        let (i,r) = parse_object(i)?;
+        let (i,r) = memory.read_object(i)?;
         match r {
             Repr::RPointer(n) => {
-                let rc = memory.point_back2(n).map_err(|e|e.to_nom(i))?;
+                let rc = memory.point_back2(n).map_err(|e|e.into_nom(i))?;
                 Ok((i,rc))
             }
             Repr::RString(s) => {
-                let data = f(s).map_err(|e|e.to_nom(i))?;
+                let data = f(s).map_err(|e|e.into_nom(i))?;
                 let rc = Rc::new(data);
                 memory.push(rc.clone());
                 Ok((i,rc))
@@ -314,9 +822,33 @@ pub fn string<'b,F,T:'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&
     }
 }
 
-pub fn int<'b,'a>(memory: &'a mut Memory, i:&'b[u8]) -> IResult<&'b[u8],i64,E>
+// An OCaml bool, i.e. the int 0 or 1
+pub fn bool_val<'b>(memory: &mut Memory, i:&'b[u8]) -> IResult<&'b[u8],bool,E>
+{
+    let (i,r) = memory.read_object(i)?;
+    match r {
+        Repr::RInt(0) => Ok((i,false)),
+        Repr::RInt(1) => Ok((i,true)),
+        _ => fail(i, format!("Expected bool, got {:?}", r))
+    }
+}
+
+// A string kept as raw bytes, for payloads that needn't be UTF-8 such as VM bytecode
+pub fn bytes<'b>(memory: &mut Memory, i:&'b[u8]) -> IResult<&'b[u8],Rc<Vec<u8>>,E> {
+    string(Ok)(memory,i)
+}
+
+// A string of exactly N bytes, e.g. a 16-byte MD5 digest
+pub fn bytes_exact<'b,const N: usize>() -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<[u8;N]>,E> {
+    string(|data: Vec<u8>| {
+        let len = data.len();
+        <[u8;N]>::try_from(data).map_err(|_|SemanticError::new(format!("Expected string of length {}, got length {}", N, len)))
+    })
+}
+
+pub fn int<'b>(memory: &mut Memory, i:&'b[u8]) -> IResult<&'b[u8],i64,E>
 {
-    let (i,r) = parse_object(i)?;
+    let (i,r) = memory.read_object(i)?;
     match r {
         Repr::RInt(n) => {
             Ok((i,n))
@@ -325,40 +857,235 @@ pub fn int<'b,'a>(memory: &'a mut Memory, i:&'b[u8]) -> IResult<&'b[u8],i64,E>
     }
 }
 
-pub fn block<'b,F,T:'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<T>,E>
-    where F:Fn(usize, &mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
+// An integer custom block, or a pointer to one, when get accepts its representation
+fn custom_int<'b,T:Copy+'static>(memory: &mut Memory, i: &'b[u8], what: &str, get: fn(&Repr) -> Option<T>) -> IResult<&'b[u8],T,E> {
+    let (i,r) = memory.read_object(i)?;
+    if let Repr::RPointer(n) = r {
+        let rc: Rc<T> = memory.point_back2(n).map_err(|e|e.into_nom(i))?;
+        return Ok((i,*rc));
+    }
+    match get(&r) {
+        Some(n) => {
+            memory.push(Rc::new(n));
+            Ok((i,n))
+        }
+        None => fail(i, format!("Expected {} or pointer to one, got {:?}", what, r))
+    }
+}
+
+// An OCaml Int32.t, a custom block with identifier _i
+pub fn int32_val<'b>(memory: &mut Memory, i:&'b[u8]) -> IResult<&'b[u8],i32,E> {
+    custom_int(memory, i, "Int32 (_i)", |r| match r { Repr::RInt32(n) => Some(*n), _ => None })
+}
+
+// An OCaml Int64.t, a custom block with identifier _l
+pub fn int64_val<'b>(memory: &mut Memory, i:&'b[u8]) -> IResult<&'b[u8],i64,E> {
+    custom_int(memory, i, "Int64 (_l)", |r| match r { Repr::RInt64(n) => Some(*n), _ => None })
+}
+
+// An OCaml Nativeint.t, a custom block with identifier _n written as 32 or 64 bits
+pub fn nativeint_val<'b>(memory: &mut Memory, i:&'b[u8]) -> IResult<&'b[u8],i64,E> {
+    custom_int(memory, i, "Nativeint (_n)", |r| match r { Repr::RNativeInt(n) => Some(*n), _ => None })
+}
+
+// An OCaml int that must be in min..=max, e.g. the constructor of a variant without arguments
+pub fn ranged_int<'b>(min: i64, max: i64) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],i64,E> {
+    move|memory,i| {
+        let (i,n) = int(memory, i)?;
+        if n < min || n > max {
+            return fail(i, format!("value {} out of range {}..={}{}", n, min, max, memory.field_context()));
+        }
+        Ok((i,n))
+    }
+}
+
+// An OCaml char, i.e. an int from 0 to 255. The byte becomes the char with that code point, as in Latin-1.
+pub fn char_val<'b>(memory: &mut Memory, i:&'b[u8]) -> IResult<&'b[u8],char,E> {
+    let (newi,r) = memory.read_object(i)?;
+    match r {
+        Repr::RInt(n @ 0..=255) => Ok((newi,char::from(n as u8))),
+        _ => fail(newi, format!("Expected char, got {:?}", r))
+    }
+}
+
+// A boxed float, e.g. a Float64 primitive. Like strings, these can be shared.
+pub fn float<'b>(memory: &mut Memory, i:&'b[u8]) -> IResult<&'b[u8],Rc<f64>,E>
+{
+    let (i,r) = memory.read_object(i)?;
+    match r {
+        Repr::RPointer(n) => {
+            let rc = memory.point_back2(n).map_err(|e|e.into_nom(i))?;
+            Ok((i,rc))
+        }
+        Repr::RDouble(x) => {
+            let rc = Rc::new(x);
+            memory.push(rc.clone());
+            Ok((i,rc))
+        }
+        _ => fail(i, format!("Expected float or pointer to float, got {:?}", r))
+    }
+}
+
+// A primitive 63-bit integer. 64-bit OCaml writes these as plain ints and 32-bit OCaml as
+// custom blocks, which can be shared.
+pub fn uint63<'b>(memory: &mut Memory, i:&'b[u8]) -> IResult<&'b[u8],Rc<Uint63>,E>
+{
+    let (i,r) = memory.read_object(i)?;
+    match r {
+        // values of 2^62 and up come out of 63-bit ints as negative
+        Repr::RInt(n) => Ok((i,Rc::new(Uint63::wrap(n as u64)))),
+        Repr::RPointer(n) => {
+            let rc = memory.point_back2(n).map_err(|e|e.into_nom(i))?;
+            Ok((i,rc))
+        }
+        Repr::RInt63(n) => {
+            let rc = Rc::new(n);
+            memory.push(rc.clone());
+            Ok((i,rc))
+        }
+        _ => fail(i, format!("Expected uint63 or pointer to uint63, got {:?}", r))
+    }
+}
+
+// A block with any tag, e.g. a variant constructor with arguments; f gets the tag and length
+pub fn tagged_block<'b,F,T:'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<T>,E>
+    where F:Fn(u8, usize, &mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
 {
     move|memory,i| {
-        let (i,r) = parse_object(i)?;
+        let (i,r) = memory.read_object(i)?;
         match r {
             Repr::RPointer(n) => {
-                let rc = memory.point_back2(n).map_err(|e|e.to_nom(i))?;
+                let rc = memory.point_back2(n).map_err(|e|e.into_nom(i))?;
                 Ok((i,rc))
             }
-            Repr::RBlock(0,len) if len>0 => {
+            Repr::RBlock(tag,len) if len>0 => {
                 let index = memory.reserve_for_struct();
-                let (i,data) = f(len, memory, i)?;
+                let (i,data) = f(tag, len, memory, i)?;
                 let rc = memory.backfill_struct2(index, data);
                 Ok((i,rc))
             }
-            _ => fail(i, format!("Expected block or pointer to array, got {:?}", r))
+            _ => fail(i, format!("Expected block or pointer to block, got {:?}", r))
         }
     }
 }
 
+pub fn block<'b,F,T:'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<T>,E>
+    where F:Fn(usize, &mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
+{
+    tagged_block(move|tag,len,memory,i| {
+        if tag == 0 {
+            f(len, memory, i)
+        } else {
+            fail(i, format!("Expected block with tag 0, got tag {}", tag))
+        }
+    })
+}
+
+// A block read as a tuple or record of the given arity that turned out to have another length.
+// Often the schema wants an array there instead, or the other way round.
+fn wrong_arity<'b,T>(memory: &Memory, i: &'b[u8], arity: usize, len: usize) -> IResult<&'b[u8],T,E> {
+    fail(i, format!("Expected a tuple of {} fields, got a block of {} fields{}", arity, len, memory.field_context()))
+}
+
+// The elements of an array or other variable-length block, once its header has been read
+fn elements<'b,F,T>(f: &F, len: usize, memory: &mut Memory, i: &'b[u8]) -> IResult<&'b[u8],Vec<T>,E>
+    where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
+{
+    // every element takes at least a byte, so don't trust len further than that
+    let mut items = Vec::with_capacity(len.min(i.len()));
+    let mut i = i;
+    for _ in 0..len {
+        memory.options().check_cancelled(i)?;
+        let (newi, d) = f(memory, i)?;
+        i = newi;
+        items.push(d);
+    }
+    Ok((i,items))
+}
+
 pub fn vec<'b,F,T:'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<Vec<T>>,E>
     where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
 {
-    block(move|len,memory,i| {
-        let mut nblock = Vec::with_capacity(len);
+    block(move|len,memory,i|elements(&f, len, memory, i))
+}
+
+// An OCaml array. Unlike vec, the empty array (an atom rather than a block) is allowed, and the
+// length is checked against the data left and the heap size in the header before reading any
+// elements, so that a tuple or a damaged length is caught early.
+pub fn array<'b,F,T:'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<Vec<T>>,E>
+    where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
+{
+    let nonempty = tagged_block(move|tag,len,memory,i| {
+        if tag != 0 {
+            return fail(i, format!("Expected an array, got a block with tag {} and {} fields{}", tag, len, memory.field_context()));
+        }
+        if len > i.len() {
+            return fail(i, format!("Array of {} elements, but only {} bytes of data left{}", len, i.len(), memory.field_context()));
+        }
+        if let Some(words) = memory.words {
+            if len >= words {
+                return fail(i, format!("Array of {} elements needs {} heap words, but the header declares {} for the whole segment{}", len, len + 1, words, memory.field_context()));
+            }
+        }
+        elements(&f, len, memory, i)
+    });
+    move|memory,i| {
+        let (newi,r) = memory.read_object(i)?;
+        match r {
+            Repr::RBlock(0,0) => Ok((newi,Rc::new(vec![]))),
+            _ => nonempty(memory,i)
+        }
+    }
+}
+
+// What the table holds for each cons cell of a list: the list read from that cell on, as a
+// position in the whole list, so a pointer to a shared tail needn't copy it up front
+struct ListTail<T> {
+    list: Rc<Vec<T>>,
+    start: usize
+}
+
+// An OCaml list: [] is the int 0 and x :: xs a block of tag 0 and length 2. Walks the cons cells
+// in a loop rather than recursing, since lists can be far longer than the stack is deep.
+pub fn list<'b,F,T:Clone+'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<Vec<T>>,E>
+    where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
+{
+    move|memory,i| {
+        let mut items = vec![];
+        let mut cells = vec![];
         let mut i = i;
-        for _ in 0..len {
-            let (newi, d) = f(memory, i)?;
-            i = newi;
-            nblock.push(d);
+        loop {
+            memory.options().check_cancelled(i)?;
+            let (newi,r) = memory.read_object(i)?;
+            match r {
+                Repr::RInt(0) => {
+                    i = newi;
+                    break;
+                }
+                Repr::RBlock(0,2) => {
+                    cells.push(memory.reserve_for_struct());
+                    let (newi,x) = f(memory, newi)?;
+                    items.push(x);
+                    i = newi;
+                }
+                Repr::RPointer(n) => {
+                    let tail: Rc<ListTail<T>> = memory.point_back2(n).map_err(|e|e.into_nom(newi))?;
+                    if items.is_empty() && tail.start == 0 {
+                        return Ok((newi,tail.list.clone()));
+                    }
+                    items.extend_from_slice(&tail.list[tail.start..]);
+                    i = newi;
+                    break;
+                }
+                _ => return fail(newi, format!("Expected list, got {:?}", r))
+            }
         }
-        Ok((i,nblock))
-    })
+        let list = Rc::new(items);
+        for (start,cell) in cells.into_iter().enumerate() {
+            memory.backfill_struct2(cell, ListTail{list: list.clone(), start});
+        }
+        Ok((i,list))
+    }
 }
 
 pub fn block1<'b,F,M,T:'static,R:'static>(f:F,m:M) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<R>,E>
@@ -368,10 +1095,10 @@ pub fn block1<'b,F,M,T:'static,R:'static>(f:F,m:M) -> impl Fn(&mut Memory, &'b[u
     block(move|len,memory,i| {
         if len == 1 {
             let (i,a) = f(memory, i)?;
-            let data = m(a).map_err(|e|e.to_nom(i))?;
+            let data = m(a).map_err(|e|e.into_nom(i))?;
             Ok((i,data))
         } else {
-            fail(i, format!("tuple1: actual block length was {}", len))
+            wrong_arity(memory, i, 1, len)
         }
     })
 }
@@ -385,10 +1112,10 @@ pub fn block2<'b,F,G,M,T:'static,U:'static,R:'static>(f:F,g:G,m:M) -> impl Fn(&m
         if len == 2 {
             let (i,a) = f(memory, i)?;
             let (i,b) = g(memory, i)?;
-            let data = m(a,b).map_err(|e|e.to_nom(i))?;
+            let data = m(a,b).map_err(|e|e.into_nom(i))?;
             Ok((i,data))
         } else {
-            fail(i, format!("tuple2: actual block length was {}", len))
+            wrong_arity(memory, i, 2, len)
         }
     })
 }
@@ -404,10 +1131,10 @@ pub fn block3<'b,F,G,H,M,T:'static,U:'static,V:'static,R:'static>(f:F,g:G,h:H,m:
             let (i,a) = f(memory, i)?;
             let (i,b) = g(memory, i)?;
             let (i,c) = h(memory, i)?;
-            let data = m(a,b,c).map_err(|e|e.to_nom(i))?;
+            let data = m(a,b,c).map_err(|e|e.into_nom(i))?;
             Ok((i,data))
         } else {
-            fail(i, format!("tuple3: actual block length was {}", len))
+            wrong_arity(memory, i, 3, len)
         }
     })
 }
@@ -428,10 +1155,10 @@ pub fn block5<'b,F,G,H,I,J,M,T:'static,U:'static,V:'static,W:'static,X:'static,R
             let (input,c) = h(memory, input)?;
             let (input,d) = i(memory, input)?;
             let (input,e) = j(memory, input)?;
-            let data = m(a,b,c,d,e).map_err(|err|err.to_nom(input))?;
+            let data = m(a,b,c,d,e).map_err(|err|err.into_nom(input))?;
             Ok((input,data))
         } else {
-            fail(input, format!("tuple3: actual block length was {}", len))
+            wrong_arity(memory, input, 5, len)
         }
     })
 }
@@ -465,12 +1192,217 @@ pub fn my<'b,F,T:Clone+'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult
     }
 }
 
+// Parses the arguments of one constructor of a variant, given the block's length
+pub type ConstructorParser<'b,T> = Box<dyn Fn(usize, &mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E> + 'b>;
+
+// An OCaml variant. The nth constructor without arguments arrives as the int n and becomes
+// constants[n]; the one with arguments and tag k arrives as a block parsed by blocks[k].
+pub fn variant<'b,T:Clone+'static>(constants: Vec<T>, blocks: Vec<ConstructorParser<'b,T>>) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
+{
+    move|memory,i| {
+        let (newi,r) = memory.read_object(i)?;
+        match r {
+            Repr::RInt(n) => match usize::try_from(n).ok().and_then(|n|constants.get(n)) {
+                Some(c) => Ok((newi,c.clone())),
+                None => fail(newi, format!("Constant constructor {} out of range, variant has {}", n, constants.len()))
+            },
+            _ => {
+                // backtrack
+                my(tagged_block(|tag,len,memory,i| match blocks.get(tag as usize) {
+                    Some(parser) => parser(len, memory, i),
+                    None => fail(i, format!("Constructor tag {} out of range, variant has {} with arguments", tag, blocks.len()))
+                }))(memory,i)
+            }
+        }
+    }
+}
+
+// A node of an OCaml Map or Set as marshalled: an AVL tree where Empty is the int 0, a Map node
+// is a block (l, k, v, r, height) and a Set node (l, k, r, height). Subtrees can be shared.
+struct AvlNode<K,V> {
+    left: Option<Rc<AvlNode<K,V>>>,
+    key: K,
+    value: V,
+    right: Option<Rc<AvlNode<K,V>>>
+}
+
+fn avl_tree<'b,K:'static,V:'static>(entry: &dyn Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],(K,V),E>, size: usize, memory: &mut Memory, i: &'b[u8])
+    -> IResult<&'b[u8],Option<Rc<AvlNode<K,V>>>,E>
+{
+    nullable(block(|len,memory,i| {
+        if len != size {
+            return fail(i, format!("Expected tree node of size {}, got {}", size, len));
+        }
+        let (i,left) = avl_tree(entry, size, memory, i)?;
+        let (i,(key,value)) = entry(memory, i)?;
+        let (i,right) = avl_tree(entry, size, memory, i)?;
+        let (i,_height) = int(memory, i)?;
+        Ok((i,AvlNode{left, key, value, right}))
+    }))(memory,i)
+}
+
+// Visits the entries in order, without recursing
+fn avl_entries<K,V,F:FnMut(&K,&V) -> bool>(root: &Option<Rc<AvlNode<K,V>>>, mut f: F) -> bool {
+    let mut stack = vec![];
+    let mut node = root.as_ref();
+    loop {
+        while let Some(n) = node {
+            stack.push(n);
+            node = n.left.as_ref();
+        }
+        match stack.pop() {
+            Some(n) => {
+                if !f(&n.key, &n.value) {
+                    return false;
+                }
+                node = n.right.as_ref();
+            }
+            None => return true
+        }
+    }
+}
+
+// An OCaml Map.Make(K).t
+pub fn map<'b,F,G,K:Ord+Clone+'static,V:Clone+'static>(k:F, v:G) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<BTreeMap<K,V>>,E>
+    where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],K,E>,
+          G:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],V,E>
+{
+    move|memory,i| {
+        let entry = |memory: &mut Memory, i| {
+            let (i,key) = k(memory, i)?;
+            let (i,value) = v(memory, i)?;
+            Ok((i,(key,value)))
+        };
+        let (i,root) = avl_tree(&entry, 5, memory, i)?;
+        let mut result = BTreeMap::new();
+        if !avl_entries(&root, |key,value|result.insert(key.clone(), value.clone()).is_none()) {
+            return fail(i, "Map contains the same key twice".to_string());
+        }
+        Ok((i,Rc::new(result)))
+    }
+}
+
+// An OCaml Set.Make(K).t
+pub fn set<'b,F,K:Ord+Clone+'static>(k:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<BTreeSet<K>>,E>
+    where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],K,E>
+{
+    move|memory,i| {
+        let entry = |memory: &mut Memory, i| {
+            let (i,key) = k(memory, i)?;
+            Ok((i,(key,())))
+        };
+        let (i,root) = avl_tree(&entry, 4, memory, i)?;
+        let mut result = BTreeSet::new();
+        if !avl_entries(&root, |key,_|result.insert(key.clone())) {
+            return fail(i, "Set contains the same element twice".to_string());
+        }
+        Ok((i,Rc::new(result)))
+    }
+}
+
+// A binding in a chain of an OCaml Hashtbl: a block (key, value, next), with the int 0 ending the chain
+struct Bucket<K,V> {
+    key: K,
+    value: V,
+    next: Option<Rc<Bucket<K,V>>>
+}
+
+fn bucket<'b,K:'static,V:'static>(entry: &dyn Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],(K,V),E>, memory: &mut Memory, i: &'b[u8])
+    -> IResult<&'b[u8],Option<Rc<Bucket<K,V>>>,E>
+{
+    nullable(block(|len,memory,i| {
+        if len != 3 {
+            return fail(i, format!("Expected hash table bucket of size 3, got {}", len));
+        }
+        let (i,(key,value)) = entry(memory, i)?;
+        let (i,next) = bucket(entry, memory, i)?;
+        Ok((i,Bucket{key, value, next}))
+    }))(memory,i)
+}
+
+// An OCaml Hashtbl.t: a record (size, buckets) before OCaml 4.00 and (size, buckets, seed,
+// initial_size) since. A key bound more than once keeps the binding Hashtbl.find would return.
+pub fn hashtbl<'b,F,G,K:Eq+Hash+Clone+'static,V:Clone+'static>(k:F, v:G) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Rc<HashMap<K,V>>,E>
+    where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],K,E>,
+          G:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],V,E>
+{
+    move|memory,i| {
+        let entry = |memory: &mut Memory, i| {
+            let (i,key) = k(memory, i)?;
+            let (i,value) = v(memory, i)?;
+            Ok((i,(key,value)))
+        };
+        block(move|len,memory,i| {
+            if len != 2 && len != 4 {
+                return fail(i, format!("Expected Hashtbl record of size 2 or 4, got {}", len));
+            }
+            let (i,size) = int(memory, i)?;
+            let (mut i,buckets) = vec(|memory: &mut Memory, i|bucket(&entry, memory, i))(memory, i)?;
+            if len == 4 {
+                i = int(memory, i)?.0;
+                i = int(memory, i)?.0;
+            }
+            let mut result = HashMap::new();
+            let mut count = 0;
+            for chain in buckets.iter() {
+                let mut b = chain.as_ref();
+                while let Some(binding) = b {
+                    result.entry(binding.key.clone()).or_insert_with(||binding.value.clone());
+                    count += 1;
+                    b = binding.next.as_ref();
+                }
+            }
+            if count != size {
+                return fail(i, format!("Hashtbl size is {}, but its buckets hold {} bindings", size, count));
+            }
+            Ok((i,result))
+        })(memory,i)
+    }
+}
+
+const LAZY_TAG:u8 = 246;
+const FORWARD_TAG:u8 = 250;
+
+// An OCaml Lazy.t that has been forced: either the value itself, or a block with Forward_tag around
+// it. An unforced one holds the closure that would compute it, so it can't be read.
+pub fn lazy_val<'b,F,T:Clone+'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
+    where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
+{
+    move|memory,i| {
+        let (newi,r) = memory.read_object(i)?;
+        match r {
+            Repr::RBlock(FORWARD_TAG,1) => my(tagged_block(|_,_,memory,i|f(memory,i)))(memory,i),
+            Repr::RBlock(LAZY_TAG,_) => fail(newi, "Lazy value was never forced, so it holds a closure rather than the value".to_string()),
+            // backtrack
+            _ => f(memory,i)
+        }
+    }
+}
+
+// A code pointer, when the policy is Opaque. None under Skip.
+pub fn code_pointer<'b>(memory: &mut Memory, i:&'b[u8]) -> IResult<&'b[u8],Option<CodePointer>,E>
+{
+    let (i,r) = memory.read_object(i)?;
+    match r {
+        Repr::RCodePointer{offset,digest} => Ok((i,Some(CodePointer{offset,digest}))),
+        Repr::RInt(0) if memory.options().code_pointers == CodePointerPolicy::Skip => Ok((i,None)),
+        _ => fail(i, format!("Expected code pointer, got {:?}", r))
+    }
+}
+
+// Where a marshalled function's code lives: an offset into the code fragment with the given digest
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct CodePointer {
+    pub offset: u32,
+    pub digest: DigestBytes
+}
+
 // Treats int(0) as a special null value
 pub fn nullable<'b,F,T:Clone+'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Option<T>,E>
     where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>,
 {
     move|memory,i| {
-        let (newi,r) = parse_object(i)?;
+        let (newi,r) = memory.read_object(i)?;
         match r {
             Repr::RInt(0) => {
                 Ok((newi,None))
@@ -484,50 +1416,243 @@ pub fn nullable<'b,F,T:Clone+'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> I
     }
 }
 
+// An OCaml option: None is the int 0 and Some x a block of tag 0 and length 1 holding x
+pub fn opt<'b,F,T:Clone+'static>(f:F) -> impl Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],Option<T>,E>
+    where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>,
+{
+    let some = block(move|len,memory,i| {
+        if len == 1 {
+            f(memory,i)
+        } else {
+            fail(i, format!("Expected Some with 1 field, got {} fields", len))
+        }
+    });
+    move|memory,i| {
+        let (newi,r) = memory.read_object(i)?;
+        match r {
+            Repr::RInt(0) => Ok((newi,None)),
+            Repr::RInt(n) => fail(newi, format!("Expected None or Some, got int {}", n)),
+            _ => {
+                // backtrack
+                let (i,rc) = some(memory,i)?;
+                Ok((i,Some(unshare(rc))))
+            }
+        }
+    }
+}
 
 pub fn as_string(string: &[u8]) -> String {
-    let result = std::str::from_utf8(string);
-    if result.is_ok() {
-        result.unwrap().to_string()
-    } else {
-        format!("{:?}", string)
+    match std::str::from_utf8(string) {
+        Ok(s) => s.to_string(),
+        Err(_) => format!("{:?}", string)
     }
 }
 
-fn segment<'b,'a:'b,F,T:Clone+Sized+'static>(f:F, file_len: usize, i:&'b[u8]) -> IResult<&'b[u8],(T,usize,DigestBytes),E>
-    where F:Fn(&mut Memory, &'b[u8]) -> IResult<&'b[u8],T,E>
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct SegmentInfo {
+    pub stop: usize,
+    // Length of the data as stored, which is compressed if compressed is set
+    pub len: usize,
+    pub digest: DigestBytes,
+    pub compressed: bool
+}
+
+impl SegmentInfo {
+    // The marshalled data, given the whole file
+    pub fn data<'a>(&self, contents: &'a[u8]) -> &'a[u8] {
+        &contents[self.stop - self.len..self.stop]
+    }
+    pub fn digest_ok(&self, contents: &[u8]) -> bool {
+        DigestBytes::new(&md5(self.data(contents))) == self.digest
+    }
+}
+
+// Moves every position in an error to the start of at, for errors found in data that isn't part
+// of the file as such (i.e. decompressed data)
+fn relocate(e: nom::Err<E>, at: &[u8]) -> nom::Err<E> {
+    let at_start = |mut e: E| {
+        for entry in &mut e.stuff {
+            entry.0 = at.len();
+        }
+        e
+    };
+    match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => nom::Err::Failure(at_start(e)),
+        nom::Err::Incomplete(n) => nom::Err::Incomplete(n)
+    }
+}
+
+fn segment<'b,F,T:Clone+Sized+'static>(f:F, file_len: usize, options: &ParseOptions, i:&'b[u8]) -> IResult<&'b[u8],(T,SegmentInfo),E>
+    where F:for<'c> Fn(&mut Memory, &'c[u8]) -> IResult<&'c[u8],T,E>
 {
     let (i,stop) = be_i32(i)?;
-    let (i,(len,_,_,size)) = header(i)?;
-    let orig_pos = i.len();
-    let mut memory= Memory::with_capacity(size as usize);
-    let (i,obj) = f(&mut memory,i)?;
-    if memory.len() != size as usize {
-        return fail(i, format!("Memory should be length {}, was actually {}", size, memory.len()));
+    options.check_cancelled(i)?;
+    let (i,h) = header(i)?;
+    h.check().map_err(|msg|nom::Err::Failure(E::new(i,msg)))?;
+    let (len,size) = (h.data_len as usize, h.objects as usize);
+    let (rest,raw) = take(len)(i)?;
+    let data = decompress(&h, raw).map_err(|msg|nom::Err::Failure(E::new(i,msg)))?;
+    let mut memory= Memory::with_capacity(size.min(data.len()), options);
+    memory.expected = Some(size);
+    memory.words = Some(h.size64 as usize).filter(|&w|w != 0);
+    let (i,obj) = match data {
+        Cow::Borrowed(_) => {
+            // parse in place, so that error positions are positions in the file
+            let orig_pos = i.len();
+            let (i,obj) = f(&mut memory,i)?;
+            if orig_pos - i.len() != len {
+                return fail(i, format!("Expected to consume {} bytes, actually consumed {}", len, orig_pos - i.len()));
+            }
+            (i,obj)
+        }
+        Cow::Owned(data) => {
+            let (tail,obj) = f(&mut memory,&data).map_err(|e|relocate(e, i))?;
+            if !tail.is_empty() {
+                return fail(i, format!("Expected to consume {} decompressed bytes, actually consumed {}", data.len(), data.len() - tail.len()));
+            }
+            (rest,obj)
+        }
+    };
+    if memory.len() != size {
+        return fail(i, format!("Object count mismatch: header says {} shared objects, parsing allocated {}", size, memory.len()));
     }
-    if orig_pos - i.len() != len as usize {
-        return fail(i, format!("Expected to consume {} bytes, actually consumed {}", len, orig_pos - i.len()));
+    if file_len - i.len() != stop as usize {
+        return fail(i, format!("Expected to stop at {}, actually stopped at {}", stop, file_len - i.len()));
     }
+    let (i,digest) = take(16usize)(i)?;
+
+    Ok((i,(obj,SegmentInfo{stop:stop as usize, len, digest:DigestBytes::new(digest), compressed:h.kind == HeaderKind::Compressed})))
+}
+
+// For segments whose types we don't model yet: step over the marshalled data without parsing it
+fn skip_segment(file_len: usize, i:&[u8]) -> IResult<&[u8],SegmentInfo,E> {
+    let (i,stop) = be_i32(i)?;
+    let (i,h) = header(i)?;
+    let len = h.data_len as usize;
+    let (i,_) = take(len)(i)?;
     if file_len - i.len() != stop as usize {
         return fail(i, format!("Expected to stop at {}, actually stopped at {}", stop, file_len - i.len()));
     }
     let (i,digest) = take(16usize)(i)?;
 
-    Ok((i,(obj,stop as usize,DigestBytes::new(digest))))
+    Ok((i,SegmentInfo{stop:stop as usize, len, digest:DigestBytes::new(digest), compressed:h.kind == HeaderKind::Compressed}))
 }
 
-fn md5(i: &[u8]) -> Vec<u8> {
+pub fn md5(i: &[u8]) -> Vec<u8> {
     let mut hasher = Md5::new();
     hasher.input(i);
     hasher.result().to_vec()
 }
 
-fn file_contents(i: &[u8]) -> IResult<&[u8],(),E> {
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct VoFile {
+    pub magic: i32,
+    pub summary: SummaryDisk,
+    pub segments: Vec<SegmentInfo>,
+    // For each segment, the file offset of each cell in the shared-object table, in cell order.
+    // Only recorded if ParseOptions::record_offsets was set, otherwise empty.
+    pub offsets: Vec<Vec<usize>>
+}
+
+impl VoFile {
+    // The Coq release that wrote this file, known since only known magic numbers are accepted
+    pub fn coq_version(&self) -> &'static str {
+        coq_version(self.magic).unwrap_or("?")
+    }
+    // What a segment holds, e.g. "summary" or "opaques"
+    pub fn segment_name(&self, segment: usize) -> Option<&'static str> {
+        layout(self.magic)?.segments.get(segment).copied()
+    }
+    // The digest that other libraries record for this one in their deps
+    pub fn library_digest(&self) -> Option<&DigestBytes> {
+        self.segments.get(1).map(|s|&s.digest)
+    }
+    // Indices of the segments whose data doesn't match the digest recorded after it. This checks
+    // whole segments; entries within the opaque proof table aren't decoded, so aren't checked.
+    pub fn bad_digests(&self, contents: &[u8]) -> Vec<usize> {
+        (0..self.segments.len()).filter(|&n|!self.segments[n].digest_ok(contents)).collect()
+    }
+    pub fn offset_of(&self, segment: usize, cell: usize) -> Option<usize> {
+        self.offsets.get(segment)?.get(cell).copied()
+    }
+    // The (segment, cell) of the last shared object starting at or before a file offset, i.e. the
+    // object whose encoding contains that byte or the innermost shared object enclosing it
+    pub fn cell_at(&self, offset: usize) -> Option<(usize,usize)> {
+        let segment = self.segments.iter().position(|s|s.stop - s.len <= offset && offset < s.stop)?;
+        let cells = self.offsets.get(segment)?;
+        match cells.binary_search(&offset) {
+            Ok(cell) => Some((segment,cell)),
+            Err(0) => None,
+            Err(cell) => Some((segment,cell - 1))
+        }
+    }
+}
+
+// File offsets of the objects that OCaml enters in its shared-object table: everything except
+// ints, atoms and pointers. Stops at the first object that can't be decoded.
+fn cell_offsets(data: &[u8], base: usize) -> Vec<usize> {
+    ObjectIter::new(data)
+        .map_while(|r|r.ok())
+        .filter(|(_,r)|!matches!(r, Repr::RInt(_) | Repr::RPointer(_) | Repr::RCodePointer{..} | Repr::RBlock(_,0)))
+        .map(|(offset,_)|base + offset)
+        .collect()
+}
+
+// One segment of a .vo file, parsed on its own
+#[derive(Debug,Clone)]
+pub struct Segment<T> {
+    // What the segment holds, from the file's layout, e.g. "summary"
+    pub name: &'static str,
+    // File offsets of the marshalled data
+    pub range: Range<usize>,
+    // As recorded in the file, not checked against the data
+    pub digest: DigestBytes,
+    pub value: T
+}
+
+// Parses segment n of a .vo file with f, skipping the segments before it without parsing them
+pub fn parse_segment<T,F>(contents: &[u8], n: usize, f: F, options: &ParseOptions) -> Result<Segment<T>,E>
+    where T:Clone+'static, F:for<'c> Fn(&mut Memory, &'c[u8]) -> IResult<&'c[u8],T,E>
+{
+    let unwrap = |e: nom::Err<E>| match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e,
+        nom::Err::Incomplete(_) => E::new(&[], "Incomplete input".to_string())
+    };
+    let file_len = contents.len();
+    let (mut i,(_,layout)) = vo_magic(contents).map_err(unwrap)?;
+    let name = match layout.segments.get(n) {
+        Some(name) => name,
+        None => return Err(E::new(i, format!("Segment {} requested, but the file has {}", n, layout.segments.len())))
+    };
+    for _ in 0..n {
+        i = skip_segment(file_len, i).map_err(unwrap)?.0;
+    }
+    let (_,(value,info)) = segment(f, file_len, options, i).map_err(unwrap)?;
+    Ok(Segment{name, range:info.stop - info.len..info.stop, digest:info.digest, value})
+}
+
+fn file_contents<'b>(options: &ParseOptions, i: &'b[u8]) -> IResult<&'b[u8],VoFile,E> {
     let entire_file = i;
     let file_len = i.len();
-    let (i,_) = vo_magic(i)?;
-    let (i,(summary_disk,_,_)) = segment(SummaryDisk::parse_val,file_len,i)?;
+    if file_len < MIN_VO_LEN {
+        return fail(i, format!("File too small to be a .vo ({} bytes)", file_len));
+    }
+    let (i,(magic,layout)) = vo_magic(i)?;
+    let (mut i,(summary_disk,summary_segment)) = segment(SummaryDisk::parse_val,file_len,options,i)?;
     debug!("{:#?}", summary_disk);
+    let mut segments = vec![summary_segment];
+    while segments.len() < layout.segments.len() && !i.is_empty() {
+        options.check_cancelled(i)?;
+        let (newi,info) = skip_segment(file_len,i)?;
+        i = newi;
+        segments.push(info);
+    }
+    if segments.len() != layout.segments.len() {
+        return fail(i, format!("Expected {} segments ({}), found {}", layout.segments.len(), layout.segments.join(", "), segments.len()));
+    }
+    if !i.is_empty() && !options.allow_trailing {
+        return fail(i, format!("{} trailing bytes after the last segment, from {:#x}", i.len(), file_len - i.len()));
+    }
 /*    let (i,(_library_disk,_,digest)) = segment(file_len,i)?;
     let (i,(_opaque_csts,_,udg)) = segment(file_len,i)?;
     let (i,(_tasks,_,_)) = segment(file_len,i)?;
@@ -538,9 +1663,739 @@ fn file_contents(i: &[u8]) -> IResult<&[u8],(),E> {
         fail::<()>(i, &format!("Checksum mismatch. Should be {:?}, was {:?}", checksum, actual_checksum))?;
     }
     debug!("pos = {}, checksum = {:?}", pos, checksum);*/
-    Ok((i,()))
+    let offsets = if options.record_offsets {
+        // offsets in decompressed data aren't file offsets, so compressed segments get none
+        segments.iter().map(|s|if s.compressed { vec![] } else { cell_offsets(s.data(entire_file), s.stop - s.len) }).collect()
+    } else {
+        vec![]
+    };
+    Ok((i,VoFile{magic, summary:summary_disk, segments, offsets}))
 }
 
-pub fn file(i: &[u8]) -> IResult<&[u8],(),E> {
-    all_consuming(file_contents)(i)
+pub fn file(i: &[u8]) -> IResult<&[u8],VoFile,E> {
+    file_with_options(&ParseOptions::default(), i)
+}
+
+// Consumes the whole input unless options.allow_trailing is set
+pub fn file_with_options<'b>(options: &ParseOptions, i: &'b[u8]) -> IResult<&'b[u8],VoFile,E> {
+    file_contents(options,i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The error from a parse that should have failed
+    fn expect_failure<T>(result: Result<T,nom::Err<E>>) -> E {
+        match result {
+            Err(nom::Err::Failure(e)) => e,
+            r => panic!("expected a failure, got {:?}", r.map(|_|()))
+        }
+    }
+
+    #[test]
+    fn object_iter_offsets() {
+        let data = [0x41, 0x24, b'I', b'n', b'i', b't', CODE_SHARED8, 1, 0xa0];
+        let objects: Vec<(usize,Repr)> = ObjectIter::new(&data).map(|r|r.unwrap()).collect();
+        let offsets: Vec<usize> = objects.iter().map(|(o,_)|*o).collect();
+        assert_eq!(offsets, vec![0, 1, 6, 8]);
+        assert!(matches!(objects[1].1, Repr::RString(ref s) if s == b"Init"));
+        assert!(matches!(objects[2].1, Repr::RPointer(1)));
+        assert!(matches!(objects[3].1, Repr::RBlock(0,2)));
+    }
+
+    #[test]
+    fn cancelled_parse_fails() {
+        let token = CancelToken::new();
+        let options = ParseOptions{cancel: Some(token.clone()), ..ParseOptions::default()};
+        let sample = include_bytes!("selftest.vo");
+        assert!(file_with_options(&options, sample).is_ok());
+        token.cancel();
+        assert_eq!(expect_failure(file_with_options(&options, sample)).stuff[0].1, "Parse cancelled");
+    }
+
+    #[test]
+    fn doubles_in_either_byte_order() {
+        let mut data = vec![CODE_DOUBLE_BIG];
+        data.extend_from_slice(&1.5f64.to_be_bytes());
+        data.push(CODE_DOUBLE_LITTLE);
+        data.extend_from_slice(&(-0.25f64).to_le_bytes());
+        let objects: Vec<Repr> = ObjectIter::new(&data).map(|r|r.unwrap().1).collect();
+        assert!(matches!(objects[0], Repr::RDouble(x) if x == 1.5));
+        assert!(matches!(objects[1], Repr::RDouble(x) if x == -0.25));
+    }
+
+    #[test]
+    fn shared_float() {
+        let mut data = vec![0xa0, CODE_DOUBLE_LITTLE];
+        data.extend_from_slice(&2.0f64.to_le_bytes());
+        data.extend_from_slice(&[CODE_SHARED8, 1]);
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(2, &options);
+        let (_,pair) = tuple2(float, float)(&mut memory, &data).unwrap();
+        assert_eq!((*pair.0, *pair.1), (2.0, 2.0));
+        assert!(Rc::ptr_eq(&pair.0, &pair.1));
+    }
+
+    #[test]
+    fn raw_bytes() {
+        // ("\xff\x00", <pointer to the same string>)
+        let data = [0xa0, 0x22, 0xff, 0x00, CODE_SHARED8, 1];
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(2, &options);
+        let (_,pair) = tuple2(bytes, bytes)(&mut memory, &data).unwrap();
+        assert_eq!(*pair.0, vec![0xff, 0x00]);
+        assert!(Rc::ptr_eq(&pair.0, &pair.1));
+        let mut memory = Memory::with_capacity(2, &options);
+        assert!(tuple2(String::parse_ref, String::parse_ref)(&mut memory, &data).is_err());
+    }
+
+    #[test]
+    fn invalid_utf8_ident() {
+        use crate::types::Ident;
+        // "a\xff"
+        let data = [0x22, b'a', 0xff];
+        for utf8 in [Utf8Policy::Error, Utf8Policy::Warn, Utf8Policy::Preserve] {
+            let options = ParseOptions{utf8, ..ParseOptions::default()};
+            let mut memory = Memory::with_capacity(1, &options);
+            let result = Ident::parse_ref(&mut memory, &data);
+            match utf8 {
+                Utf8Policy::Error => assert_eq!(expect_failure(result).stuff[0].1, "Identifier [97, 255] is not valid UTF-8: invalid utf-8 sequence of 1 bytes from index 1"),
+                Utf8Policy::Warn | Utf8Policy::Preserve => assert_eq!(result.unwrap().1.as_bytes(), b"a\xff")
+            }
+        }
+    }
+
+    #[test]
+    fn offset_index() {
+        let sample = include_bytes!("selftest.vo");
+        let (_,vo) = file(sample).unwrap();
+        assert!(vo.offsets.is_empty());
+        let options = ParseOptions{record_offsets: true, ..ParseOptions::default()};
+        let (_,vo) = file_with_options(&options, sample).unwrap();
+        // magic, stop offset, marshal header
+        let first = 4 + 4 + 20;
+        assert_eq!(vo.offset_of(0, 0), Some(first));
+        assert_eq!(vo.cell_at(first), Some((0,0)));
+        assert_eq!(vo.cell_at(first + 1), Some((0,1)));
+        assert_eq!(vo.cell_at(first - 1), None);
+        assert_eq!(vo.offsets[0].len(), vo.offsets[0].iter().collect::<std::collections::BTreeSet<_>>().len());
+        for (cell,&offset) in vo.offsets[0].iter().enumerate() {
+            assert_eq!(vo.cell_at(offset), Some((0,cell)));
+        }
+    }
+
+    #[test]
+    fn custom_blocks() {
+        let mut data = vec![CODE_CUSTOM, b'_', b'i', 0, 0xff, 0xff, 0xff, 0xfe];
+        data.extend_from_slice(&[CODE_CUSTOM, b'_', b'l', 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+        data.extend_from_slice(&[CODE_CUSTOM, b'_', b'n', 0, 1, 0, 0, 0, 7]);
+        data.extend_from_slice(&[CODE_CUSTOM, b'_', b'n', 0, 2, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        let objects: Vec<Repr> = ObjectIter::new(&data).map(|r|r.unwrap().1).collect();
+        assert!(matches!(objects[0], Repr::RInt32(-2)));
+        assert!(matches!(objects[1], Repr::RInt64(0x1_0000_0000)));
+        assert!(matches!(objects[2], Repr::RNativeInt(7)));
+        assert!(matches!(objects[3], Repr::RNativeInt(-1)));
+    }
+
+    #[test]
+    fn custom_blocks_with_lengths() {
+        // Int64 2^32 with its sizes (8 and 8), then Zarith -258, whose sizes don't matter
+        let mut data = vec![CODE_CUSTOM_LEN, b'_', b'l', 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 1, 0, 0, 0, 0];
+        data.extend_from_slice(&[CODE_CUSTOM_LEN, b'_', b'z', 0, 0, 0, 0, 12, 0, 0, 0, 0, 0, 0, 0, 16, 1, 0, 0, 0, 2, 2, 1]);
+        let objects: Vec<Repr> = ObjectIter::new(&data).map(|r|r.unwrap().1).collect();
+        assert!(matches!(objects[0], Repr::RInt64(0x1_0000_0000)));
+        assert!(matches!(objects[1], Repr::RBigInt(ref n) if n.negative && n.magnitude == [2, 1]));
+        // an Int32 that claims to take 8 bytes
+        let bad = [CODE_CUSTOM_LEN, b'_', b'i', 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 1];
+        assert_eq!(expect_failure(parse_object(&bad)).stuff[0].1, "Custom block _i records size 8, but its size is 4");
+    }
+
+    #[test]
+    fn fixed_size_custom_blocks() {
+        let mut data = vec![CODE_CUSTOM_FIXED, b'_', b'i', 0, 0xff, 0xff, 0xff, 0xfe];
+        data.extend_from_slice(&[CODE_CUSTOM_FIXED, b'_', b'n', 0, 1, 0, 0, 0, 7]);
+        data.extend_from_slice(&[CODE_CUSTOM_FIXED, b'_', b'j', 0, 0, 0, 0, 0, 0, 0, 0, 9]);
+        let objects: Vec<Repr> = ObjectIter::new(&data).map(|r|r.unwrap().1).collect();
+        assert!(matches!(objects[0], Repr::RInt32(-2)));
+        assert!(matches!(objects[1], Repr::RNativeInt(7)));
+        assert!(matches!(objects[2], Repr::RInt63(n) if n == Uint63::new(9).unwrap()));
+        // Zarith integers vary in size, so can't be written this way
+        assert!(parse_object(&[CODE_CUSTOM_FIXED, b'_', b'z', 0, 0, 0, 0, 0, 0]).is_err());
+        assert_eq!(code_name(CODE_CUSTOM_FIXED), "CUSTOM_FIXED");
+    }
+
+    #[test]
+    fn custom_int_values() {
+        // (Int32 -2, Int64 2^32, Nativeint 7, the Int32 again)
+        let mut data = vec![0xc0, CODE_CUSTOM, b'_', b'i', 0, 0xff, 0xff, 0xff, 0xfe];
+        data.extend_from_slice(&[CODE_CUSTOM, b'_', b'l', 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+        data.extend_from_slice(&[CODE_CUSTOM, b'_', b'n', 0, 1, 0, 0, 0, 7]);
+        data.extend_from_slice(&[CODE_SHARED8, 3]);
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(4, &options);
+        let (_,v) = block(|_,memory,i| {
+            let (i,a) = int32_val(memory, i)?;
+            let (i,b) = int64_val(memory, i)?;
+            let (i,c) = nativeint_val(memory, i)?;
+            let (i,d) = crate::types::Int32::parse_val(memory, i)?;
+            Ok((i,(a,b,c,d.0)))
+        })(&mut memory, &data).unwrap();
+        assert_eq!(*v, (-2, 0x1_0000_0000, 7, -2));
+        // the wrong identifier, and a plain int
+        let mut memory = Memory::with_capacity(1, &options);
+        assert!(int64_val(&mut memory, &data[1..]).is_err());
+        assert!(nativeint_val(&mut memory, &[0x41]).is_err());
+    }
+
+    #[test]
+    fn uint63_values() {
+        // a plain int, a custom block, then a pointer back to it
+        let mut data = vec![0xa0, 0x45, 0xa0, CODE_CUSTOM, b'_', b'j', 0, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        data.extend_from_slice(&[CODE_SHARED8, 1]);
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(3, &options);
+        let (_,r) = tuple2(uint63, tuple2(uint63, uint63))(&mut memory, &data).unwrap();
+        let (a,b,c) = (*r.0, &(r.1).0, &(r.1).1);
+        assert_eq!(a.get(), 5);
+        assert_eq!(**b, Uint63::MAX);
+        assert!(Rc::ptr_eq(b, c));
+        assert_eq!(**b + a, Uint63::new(4).unwrap());
+        assert_eq!(a - **b, Uint63::new(6).unwrap());
+        assert_eq!(a / Uint63::default(), Uint63::default());
+        assert_eq!(a % Uint63::default(), a);
+        // max_int, which 64-bit OCaml writes as -1
+        let mut memory = Memory::with_capacity(0, &options);
+        let (_,m) = uint63(&mut memory, &[CODE_INT64, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).unwrap();
+        assert_eq!(*m, Uint63::MAX);
+    }
+
+    #[test]
+    fn zarith_big_integer() {
+        // -(2^64 + 2)
+        let data = [CODE_CUSTOM, b'_', b'z', 0, 1, 0, 0, 0, 9, 2, 0, 0, 0, 0, 0, 0, 0, 1];
+        let objects: Vec<Repr> = ObjectIter::new(&data).map(|r|r.unwrap().1).collect();
+        match &objects[0] {
+            Repr::RBigInt(n) => {
+                assert_eq!(n.to_string(), "-18446744073709551618");
+                assert_eq!(n.to_i128(), Some(-18446744073709551618));
+                #[cfg(feature = "num-bigint")]
+                assert_eq!(num_bigint::BigInt::from(n).to_string(), n.to_string());
+            }
+            r => panic!("expected a big integer, got {:?}", r)
+        }
+    }
+
+    #[test]
+    fn registered_custom_block() {
+        let data = [CODE_CUSTOM, b'_', b't', b'e', b's', b't', 0, 1, 2, 3, 0x41];
+        assert!(ObjectIter::new(&data).next().unwrap().is_err());
+        register_custom("_test", |i|take(3usize)(i));
+        let objects: Vec<Repr> = ObjectIter::new(&data).map(|r|r.unwrap().1).collect();
+        assert!(matches!(objects[0], Repr::RCustom(ref ident, ref payload) if ident == "_test" && payload == &[1, 2, 3]));
+        assert!(matches!(objects[1], Repr::RInt(1)));
+    }
+
+    #[test]
+    fn code_pointer_policies() {
+        let mut data = vec![CODE_CODEPOINTER, 0, 0, 1, 0];
+        data.extend_from_slice(&[7;16]);
+        let mut options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(0, &options);
+        assert!(code_pointer(&mut memory, &data).is_err());
+        options.code_pointers = CodePointerPolicy::Skip;
+        let mut memory = Memory::with_capacity(0, &options);
+        assert_eq!(code_pointer(&mut memory, &data).unwrap().1, None);
+        assert_eq!(nullable(int)(&mut memory, &data).unwrap().1, None);
+        options.code_pointers = CodePointerPolicy::Opaque;
+        let mut memory = Memory::with_capacity(0, &options);
+        let expected = CodePointer{offset: 0x100, digest: DigestBytes::new(&[7;16])};
+        assert_eq!(code_pointer(&mut memory, &data).unwrap().1, Some(expected));
+    }
+
+    #[test]
+    fn small_and_big_headers() {
+        let small = [0x84, 0x95, 0xa6, 0xbe, 0, 0, 0, 9, 0, 0, 0, 2, 0, 0, 0, 3, 0, 0, 0, 4];
+        let (rest,h) = header(&small).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(h, Header{kind:HeaderKind::Small, header_len:20, data_len:9, uncompressed_len:9, objects:2, size32:3, size64:4});
+        let mut big = vec![0x84, 0x95, 0xa6, 0xbf, 0, 0, 0, 0];
+        big.extend_from_slice(&0x1_0000_0000u64.to_be_bytes());
+        big.extend_from_slice(&5u64.to_be_bytes());
+        big.extend_from_slice(&6u64.to_be_bytes());
+        let (rest,h) = header(&big).unwrap();
+        assert!(rest.is_empty());
+        assert_eq!(h, Header{kind:HeaderKind::Big, header_len:32, data_len:0x1_0000_0000, uncompressed_len:0x1_0000_0000, objects:5, size32:0, size64:6});
+        assert!(header(&[0x84, 0x95, 0xa6, 0xbc, 0, 0, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn header_consistency() {
+        let h = Header{kind:HeaderKind::Small, header_len:20, data_len:9, uncompressed_len:9, objects:2, size32:3, size64:4};
+        assert!(h.check().is_ok());
+        assert!(Header{objects:10, ..h}.check().is_err());
+        assert!(Header{size64:1, ..h}.check().is_err());
+        assert!(Header{uncompressed_len:8, ..h}.check().is_err());
+        assert!(Header{kind:HeaderKind::Big, size32:0, ..h}.check().is_ok());
+        assert!(Header{size32:0, size64:0, ..h}.check().is_ok());
+        // a segment whose header promises more objects than its data could hold
+        let mut contents = crate::testing::vo_file(8991, &[crate::testing::int(1)]);
+        contents[4 + 4 + 11] = 5;
+        let e = expect_failure(file(&contents));
+        assert_eq!(e.stuff[0].1, "Marshal header says 5 shared objects in only 1 bytes");
+    }
+
+    #[test]
+    fn big_header_segment() {
+        // the selftest file with its summary segment's header rewritten in the big format
+        let sample = include_bytes!("selftest.vo");
+        let (magic,segments) = crate::split::split_segments(sample).unwrap();
+        let (h,data) = marshal_data(segments[0]).unwrap();
+        let mut big = vec![0x84, 0x95, 0xa6, 0xbf, 0, 0, 0, 0];
+        big.extend_from_slice(&h.data_len.to_be_bytes());
+        big.extend_from_slice(&h.objects.to_be_bytes());
+        big.extend_from_slice(&h.size64.to_be_bytes());
+        big.extend_from_slice(&data);
+        let mut rebuilt: Vec<&[u8]> = segments.clone();
+        rebuilt[0] = &big;
+        let contents = crate::split::join_segments(magic, &rebuilt);
+        let (_,vo) = file(&contents).unwrap();
+        assert_eq!(vo.summary.name().to_string(), "Mathparse.Selftest");
+        assert!(vo.bad_digests(&contents).is_empty());
+    }
+
+    // The selftest file with its summary segment rewritten with a compressed header around data
+    fn compressed_selftest(data: &[u8]) -> Vec<u8> {
+        fn push_vlq(out: &mut Vec<u8>, n: u64) {
+            let mut groups = vec![(n & 0x7f) as u8];
+            let mut n = n >> 7;
+            while n != 0 {
+                groups.push((n & 0x7f) as u8 | 0x80);
+                n >>= 7;
+            }
+            out.extend(groups.iter().rev());
+        }
+        let sample = include_bytes!("selftest.vo");
+        let (magic,segments) = crate::split::split_segments(sample).unwrap();
+        let (h,original) = marshal_data(segments[0]).unwrap();
+        let mut compressed = vec![0x84, 0x95, 0xa6, 0xbd, 0];
+        for n in &[data.len() as u64, original.len() as u64, h.objects, h.size32, h.size64] {
+            push_vlq(&mut compressed, *n);
+        }
+        compressed[4] = compressed.len() as u8;
+        compressed.extend_from_slice(data);
+        let mut rebuilt: Vec<&[u8]> = segments.clone();
+        rebuilt[0] = &compressed;
+        crate::split::join_segments(magic, &rebuilt)
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn compressed_segment() {
+        let (_,segments) = crate::split::split_segments(include_bytes!("selftest.vo")).unwrap();
+        let (_,original) = marshal_data(segments[0]).unwrap();
+        let contents = compressed_selftest(&zstd::bulk::compress(&original, 3).unwrap());
+        let (_,vo) = file(&contents).unwrap();
+        assert_eq!(vo.summary.name().to_string(), "Mathparse.Selftest");
+        assert!(vo.segments[0].compressed);
+        assert!(vo.bad_digests(&contents).is_empty());
+    }
+
+    #[test]
+    #[cfg(not(feature = "zstd"))]
+    fn compressed_segment_needs_feature() {
+        let contents = compressed_selftest(b"not really zstd");
+        assert!(expect_failure(file(&contents)).stuff[0].1.contains("zstd feature"));
+    }
+
+    #[test]
+    fn tiny_files() {
+        for len in &[0, 3, 4, 43] {
+            let contents = &include_bytes!("selftest.vo")[..*len];
+            assert_eq!(expect_failure(file(contents)).stuff[0].1, format!("File too small to be a .vo ({} bytes)", len));
+        }
+    }
+
+    #[test]
+    fn known_magic_numbers() {
+        let sample = include_bytes!("selftest.vo");
+        assert_eq!(file(sample).unwrap().1.coq_version(), "8.9");
+        assert_eq!(file(sample).unwrap().1.segment_name(4), Some("opaques"));
+        let mut contents = sample.to_vec();
+        // known releases are named even though their files aren't parsed yet
+        contents[..4].copy_from_slice(&81000i32.to_be_bytes());
+        assert_eq!(expect_failure(file(&contents)).stuff[0].1, "Coq 8.10 .vo files aren't supported yet");
+        for (magic,version) in [(81300i32, "8.13"), (81500, "8.15"), (90000, "9.0")] {
+            assert_eq!(coq_version(magic), Some(version));
+            assert_eq!(layout(magic).unwrap().framing, Framing::SegmentTable);
+            contents[..4].copy_from_slice(&magic.to_be_bytes());
+            assert_eq!(expect_failure(file(&contents)).stuff[0].1,
+                format!("Coq {} .vo files aren't supported yet: they are divided by a segment table (Coq's ObjFile), which isn't parsed", version));
+            assert!(parse_segment(&contents, 0, SummaryDisk::parse_val, &ParseOptions::default()).is_err());
+        }
+        assert_eq!(supported_magics(), vec![8991]);
+        contents[..4].copy_from_slice(&1234i32.to_be_bytes());
+        assert_eq!(expect_failure(file(&contents)).stuff[0].1, "Unknown .vo magic number 1234");
+    }
+
+    #[test]
+    fn object_count_mismatch() {
+        let sample = include_bytes!("selftest.vo");
+        // the summary's header is at 8, with its object count at 16
+        let objects = u32::from_be_bytes([sample[16], sample[17], sample[18], sample[19]]);
+        let mut contents = sample.to_vec();
+        contents[16..20].copy_from_slice(&(objects + 1).to_be_bytes());
+        assert_eq!(expect_failure(file(&contents)).stuff[0].1, format!("Object count mismatch: header says {} shared objects, parsing allocated {}", objects + 1, objects));
+        contents[16..20].copy_from_slice(&1u32.to_be_bytes());
+        assert!(expect_failure(file(&contents)).stuff[0].1.starts_with("Object count mismatch: header says 1 shared objects, parsing has already allocated 2"));
+    }
+
+    #[test]
+    fn damaged_segments_fail_their_digest() {
+        let sample = include_bytes!("selftest.vo");
+        assert!(file(sample).unwrap().1.bad_digests(sample).is_empty());
+        // the opaques segment's data is the single byte at 0x12b, which isn't parsed
+        let mut contents = sample.to_vec();
+        contents[0x12b] ^= 1;
+        let (_,vo) = file(&contents).unwrap();
+        assert_eq!(vo.bad_digests(&contents), vec![4]);
+        // and so is the digest after it
+        contents[0x12b] ^= 1;
+        contents[0x12c] ^= 1;
+        let (_,vo) = file(&contents).unwrap();
+        assert_eq!(vo.bad_digests(&contents), vec![4]);
+    }
+
+    #[test]
+    fn single_segments() {
+        let sample = include_bytes!("selftest.vo");
+        let options = ParseOptions::default();
+        let summary = parse_segment(sample, 0, SummaryDisk::parse_val, &options).unwrap();
+        assert_eq!((summary.name, summary.range.clone()), ("summary", 0x1c..0x88));
+        assert_eq!(summary.value.name().to_string(), "Mathparse.Selftest");
+        let opaques = parse_segment(sample, 4, int, &options).unwrap();
+        assert_eq!((opaques.name, opaques.range.clone(), opaques.value), ("opaques", 0x12b..0x12c, 4));
+        assert_eq!(opaques.digest, file(sample).unwrap().1.segments[4].digest);
+        assert!(parse_segment(sample, 5, int, &options).is_err());
+        assert!(parse_segment(sample, 0, int, &options).is_err());
+    }
+
+    #[test]
+    fn trailing_data() {
+        let mut contents = include_bytes!("selftest.vo").to_vec();
+        let len = contents.len();
+        // two small marshal documents, holding 1 and "ab", then a stray byte
+        contents.extend_from_slice(&[0x84, 0x95, 0xa6, 0xbe, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x41]);
+        contents.extend_from_slice(&[0x84, 0x95, 0xa6, 0xbe, 0, 0, 0, 3, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 2, 0x22, b'a', b'b', 0xff]);
+        assert_eq!(expect_failure(file(&contents)).stuff[0].1, format!("45 trailing bytes after the last segment, from {:#x}", len));
+        let options = ParseOptions{allow_trailing: true, ..ParseOptions::default()};
+        let (rest,_) = file_with_options(&options, &contents).unwrap();
+        assert_eq!(rest.len(), 45);
+        let (documents,rest) = marshal_documents(rest);
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[1].1, b"\x22ab");
+        assert_eq!(rest, [0xff]);
+    }
+
+    #[test]
+    fn variant_tags() {
+        // [A 1; B (2, 3)] as an array, for type t = A of int | B of int * int
+        let data = [0xa0, 0x90, 0x41, 0xa1, 0x42, 0x43];
+        #[derive(Debug,Clone,PartialEq)]
+        enum T { A(i64), B(i64,i64) }
+        let t = tagged_block(|tag,len,memory,i| match (tag,len) {
+            (0,1) => int(memory,i).map(|(i,n)|(i,T::A(n))),
+            (1,2) => {
+                let (i,a) = int(memory,i)?;
+                let (i,b) = int(memory,i)?;
+                Ok((i,T::B(a,b)))
+            }
+            _ => fail(i, format!("Bad constructor {} with {} arguments", tag, len))
+        });
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(3, &options);
+        let (_,v) = vec(my(t))(&mut memory, &data).unwrap();
+        assert_eq!(*v, vec![T::A(1), T::B(2, 3)]);
+        let mut memory = Memory::with_capacity(3, &options);
+        assert!(vec(int)(&mut memory, &[0xa1, 0x41]).is_err());
+    }
+
+    #[test]
+    fn variants() {
+        // [C1; A 1; C0; B (2, 3)] for type t = C0 | C1 | A of int | B of int * int
+        let data = [0xc0, 0x41, 0x90, 0x41, 0x40, 0xa1, 0x42, 0x43];
+        #[derive(Debug,Clone,PartialEq)]
+        enum T { C0, C1, A(i64), B(i64,i64) }
+        let t = || variant(vec![T::C0, T::C1], vec![
+            Box::new(|_,memory,i|int(memory,i).map(|(i,n)|(i,T::A(n)))),
+            Box::new(|_,memory,i| {
+                let (i,a) = int(memory,i)?;
+                let (i,b) = int(memory,i)?;
+                Ok((i,T::B(a,b)))
+            })
+        ]);
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(3, &options);
+        let (_,v) = vec(t())(&mut memory, &data).unwrap();
+        assert_eq!(*v, vec![T::C1, T::A(1), T::C0, T::B(2, 3)]);
+        let mut memory = Memory::with_capacity(0, &options);
+        assert!(t()(&mut memory, &[0x42]).is_err());
+        assert!(t()(&mut memory, &[0x92, 0x40]).is_err());
+    }
+
+    #[test]
+    fn options() {
+        // [Some 5; None; <the first Some again>]
+        let data = [0xb0, 0x90, 0x45, 0x40, CODE_SHARED8, 1];
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(2, &options);
+        let (_,v) = vec(opt(int))(&mut memory, &data).unwrap();
+        assert_eq!(*v, vec![Some(5), None, Some(5)]);
+        let mut memory = Memory::with_capacity(2, &options);
+        let (_,v) = Vec::<Option<String>>::parse_val(&mut memory, &[0xa0, 0x90, 0x21, b'x', 0x40]).unwrap();
+        assert_eq!(v, vec![Some("x".to_string()), None]);
+        // Some with two fields, a tagged block, and an int other than 0
+        for bad in &[&[0xa0, 0x41, 0x42][..], &[0x91, 0x41], &[0x41]] {
+            let mut memory = Memory::with_capacity(1, &options);
+            assert!(opt(int)(&mut memory, bad).is_err());
+        }
+    }
+
+    #[test]
+    fn nested_options() {
+        use crate::testing as t;
+        // Some 0 is a block, unlike nullable's None
+        let data = t::option(Some(t::int(0))).to_bytes();
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(1, &options);
+        assert_eq!(opt(int)(&mut memory, &data).unwrap().1, Some(0));
+        let cases = vec![
+            (t::option(None), None),
+            (t::option(Some(t::option(None))), Some(None)),
+            (t::option(Some(t::option(Some(t::int(0))))), Some(Some(0)))
+        ];
+        for (value,expected) in cases {
+            let data = value.to_bytes();
+            let mut memory = Memory::with_capacity(2, &options);
+            assert_eq!(Option::<Option<i64>>::parse_val(&mut memory, &data).unwrap().1, expected);
+        }
+    }
+
+    #[test]
+    fn arrays_and_tuples() {
+        use crate::testing as t;
+        let options = ParseOptions::default();
+        // [||] is an atom, which vec doesn't accept
+        let empty = t::block(0).to_bytes();
+        let mut memory = Memory::with_capacity(0, &options);
+        assert_eq!(*array(int)(&mut memory, &empty).unwrap().1, Vec::<i64>::new());
+        assert!(vec(int)(&mut memory, &empty).is_err());
+        let data = t::block(0).fields(vec![t::int(1), t::int(2), t::int(3)]).to_bytes();
+        let mut memory = Memory::with_capacity(1, &options);
+        assert_eq!(*array(int)(&mut memory, &data).unwrap().1, vec![1, 2, 3]);
+        // a triple where a pair was wanted
+        let mut memory = Memory::with_capacity(1, &options);
+        assert_eq!(expect_failure(tuple2(int, int)(&mut memory, &data)).stuff[0].1, "Expected a tuple of 2 fields, got a block of 3 fields");
+        // lengths that the data or the header can't account for
+        let mut memory = Memory::with_capacity(1, &options);
+        assert_eq!(expect_failure(array(int)(&mut memory, &[0x08, 0, 0, 0x40, 0, 0x40])).stuff[0].1, "Array of 16 elements, but only 1 bytes of data left");
+        let mut memory = Memory::with_capacity(1, &options);
+        memory.words = Some(3);
+        assert_eq!(expect_failure(array(int)(&mut memory, &data)).stuff[0].1, "Array of 3 elements needs 4 heap words, but the header declares 3 for the whole segment");
+        let tagged = t::block(1).field(t::int(0)).to_bytes();
+        let mut memory = Memory::with_capacity(1, &options);
+        assert!(array(int)(&mut memory, &tagged).is_err());
+    }
+
+    #[test]
+    fn lists() {
+        // ([1; 2; 3], [0; 2; 3]) with the tail [2; 3] shared
+        let data = [0xa0, 0xa0, 0x41, 0xa0, 0x42, 0xa0, 0x43, 0x40, 0xa0, 0x40, CODE_SHARED8, 3];
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(5, &options);
+        let (_,v) = tuple2(list(int), list(int))(&mut memory, &data).unwrap();
+        assert_eq!(v.0, Rc::new(vec![1, 2, 3]));
+        assert_eq!(v.1, Rc::new(vec![0, 2, 3]));
+        // the same list twice
+        let mut memory = Memory::with_capacity(2, &options);
+        let (_,v) = tuple2(list(int), list(int))(&mut memory, &[0xa0, 0xa0, 0x41, 0x40, CODE_SHARED8, 1]).unwrap();
+        assert!(Rc::ptr_eq(&v.0, &v.1));
+        // an array isn't a list, and a list longer than the stack is deep is fine
+        let mut memory = Memory::with_capacity(1, &options);
+        assert!(list(int)(&mut memory, &[0xa1, 0x41]).is_err());
+        let mut data = vec![];
+        for _ in 0..100_000 {
+            data.extend_from_slice(&[0xa0, 0x40]);
+        }
+        data.push(0x40);
+        let mut memory = Memory::with_capacity(100_000, &options);
+        let (_,v) = crate::types::List::<bool>::parse_val(&mut memory, &data).unwrap();
+        assert_eq!(v.0.len(), 100_000);
+    }
+
+    #[test]
+    fn ranged_ints() {
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(1, &options);
+        let (_,v) = <(u8,i32)>::parse_val(&mut memory, &[0xa0, 0x01, 0x00, 0xff, CODE_INT8, 0xff]).unwrap();
+        assert_eq!(v, (255, -1));
+        let mut memory = Memory::with_capacity(0, &options);
+        memory.enter_field("SummaryDisk", "foo");
+        let e = expect_failure(u16::parse_val(&mut memory, &[CODE_INT32, 0x00, 0x01, 0x11, 0x70]));
+        assert_eq!(e.stuff[0].1, "value 70000 out of range for u16 at SummaryDisk.foo");
+        for bad in &[&[CODE_INT8, 0xff][..], &[0x21, b'x']] {
+            let mut memory = Memory::with_capacity(1, &options);
+            assert!(usize::parse_val(&mut memory, bad).is_err());
+        }
+        // derived structs say which field
+        #[derive(Clone,vo_parse_derive::VoParse)]
+        struct Pair { a: u16, b: u8 }
+        let mut memory = Memory::with_capacity(1, &options);
+        let (_,p) = Pair::parse_val(&mut memory, &[0xa0, 0x41, 0x42]).unwrap();
+        assert_eq!((p.a, p.b), (1, 2));
+        let mut memory = Memory::with_capacity(1, &options);
+        let e = expect_failure(Pair::parse_val(&mut memory, &[0xa0, 0x41, CODE_INT16, 0x01, 0x00]));
+        assert_eq!(e.stuff[0].1, "value 256 out of range for u8 at Pair.b");
+    }
+
+    #[test]
+    fn chars_and_ranges() {
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(1, &options);
+        let (_,v) = vec(char_val)(&mut memory, &[0xb0, CODE_INT8, b'a', CODE_INT16, 0, 0xe9, 0x40]).unwrap();
+        assert_eq!(*v, vec!['a', '\u{e9}', '\0']);
+        assert!(char_val(&mut memory, &[CODE_INT16, 1, 0]).is_err());
+        assert!(char_val(&mut memory, &[CODE_INT8, 0xff]).is_err());
+        // type t = A | B | C
+        let (_,n) = ranged_int(0, 2)(&mut memory, &[0x42]).unwrap();
+        assert_eq!(n, 2);
+        let e = expect_failure(ranged_int(0, 2)(&mut memory, &[0x43]));
+        assert_eq!(e.stuff[0].1, "value 3 out of range 0..=2");
+    }
+
+    #[test]
+    fn fixed_length_bytes() {
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(1, &options);
+        let (_,v) = tuple2(bytes_exact::<3>(), bytes_exact::<3>())(&mut memory, &[0xa0, 0x23, b'a', b'b', b'c', CODE_SHARED8, 1]).unwrap();
+        assert_eq!(*v.0, *b"abc");
+        assert!(Rc::ptr_eq(&v.0, &v.1));
+        let mut memory = Memory::with_capacity(1, &options);
+        let e = expect_failure(bytes_exact::<4>()(&mut memory, &[0x23, b'a', b'b', b'c']));
+        assert_eq!(e.stuff[0].1, "Expected string of length 4, got length 3");
+        let mut memory = Memory::with_capacity(1, &options);
+        let (_,v) = <[u8;2]>::parse_val(&mut memory, &[0x22, 0xff, 0x00]).unwrap();
+        assert_eq!(v, [0xff, 0x00]);
+    }
+
+    #[test]
+    fn maps_and_sets() {
+        // {1: "a", 2: "b"} as Node(Node(Empty, 1, "a", Empty, 1), 2, "b", Empty, 2), then its left
+        // subtree again as another map
+        let data = [0xa0, 0xd0, 0xd0, 0x40, 0x41, 0x21, b'a', 0x40, 0x41, 0x42, 0x21, b'b', 0x40, 0x42, CODE_SHARED8, 3];
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(5, &options);
+        let (_,v) = tuple2(my(map(int, my(String::parse_ref))), my(map(int, my(String::parse_ref))))(&mut memory, &data).unwrap();
+        assert_eq!(v.0, vec![(1, "a".to_string()), (2, "b".to_string())].into_iter().collect());
+        assert_eq!(v.1, vec![(1, "a".to_string())].into_iter().collect());
+        // {1, 2}, a node of the wrong size, and an element twice
+        let mut memory = Memory::with_capacity(2, &options);
+        let (_,s) = set(int)(&mut memory, &[0xc0, 0xc0, 0x40, 0x41, 0x40, 0x41, 0x42, 0x40, 0x42]).unwrap();
+        assert_eq!(*s, vec![1, 2].into_iter().collect());
+        let mut memory = Memory::with_capacity(2, &options);
+        assert!(set(int)(&mut memory, &[0xd0, 0x40, 0x41, 0x41, 0x40, 0x41]).is_err());
+        let mut memory = Memory::with_capacity(2, &options);
+        assert!(set(int)(&mut memory, &[0xc0, 0xc0, 0x40, 0x41, 0x40, 0x41, 0x41, 0x40, 0x42]).is_err());
+        let mut memory = Memory::with_capacity(0, &options);
+        let (_,m) = BTreeMap::<bool,bool>::parse_val(&mut memory, &[0x40]).unwrap();
+        assert!(m.is_empty());
+    }
+
+    #[test]
+    fn lazy_values() {
+        use crate::testing as t;
+        let options = ParseOptions::default();
+        // forced and short-circuited, forced behind a forward block, and never forced
+        for (v,expected) in &[(t::str("x"), "x"), (t::block(250).field(t::str("y")), "y")] {
+            let data = v.to_bytes();
+            let mut memory = Memory::with_capacity(2, &options);
+            let (_,s) = lazy_val(my(String::parse_ref))(&mut memory, &data).unwrap();
+            assert_eq!(s, *expected);
+        }
+        let mut memory = Memory::with_capacity(1, &options);
+        let (_,v) = crate::types::Lazy::<i64>::parse_val(&mut memory, &t::block(250).field(t::int(3)).to_bytes()).unwrap();
+        assert_eq!(v.0, 3);
+        let data = t::block(246).field(t::int(0)).to_bytes();
+        let mut memory = Memory::with_capacity(1, &options);
+        assert!(lazy_val(int)(&mut memory, &data).is_err());
+    }
+
+    #[test]
+    fn hash_tables() {
+        use crate::testing as t;
+        // Two buckets, [1 -> "new"; 1 -> "old"] and [2 -> "b"], with seed 0 and initial size 16
+        let binding = |k,v,next|t::block(0).field(t::int(k)).field(t::str(v)).field(next);
+        let buckets = t::block(0).field(binding(1, "new", binding(1, "old", t::int(0)))).field(binding(2, "b", t::int(0)));
+        let mut data = t::block(0).fields(vec![t::int(3), buckets, t::int(0), t::int(16)]).to_bytes();
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(9, &options);
+        let (_,h) = hashtbl(int, my(String::parse_ref))(&mut memory, &data).unwrap();
+        assert_eq!(*h, vec![(1, "new".to_string()), (2, "b".to_string())].into_iter().collect());
+        // the size field disagreeing with the buckets
+        data[1] = 0x42;
+        let mut memory = Memory::with_capacity(9, &options);
+        assert!(hashtbl(int, my(String::parse_ref))(&mut memory, &data).is_err());
+        // the pre-4.00 record, with an empty table
+        let mut memory = Memory::with_capacity(2, &options);
+        let (_,h) = hashtbl(int, int)(&mut memory, &[0xa0, 0x40, 0x90, 0x40]).unwrap();
+        assert!(h.is_empty());
+    }
+
+    #[test]
+    fn pointer_diagnostics() {
+        assert_eq!(short_type_name("alloc::rc::Rc<alloc::vec::Vec<(mathparse::types::Ident, u8)>>"), "Rc<Vec<(Ident, u8)>>");
+        let failure = |r: IResult<&[u8],(),E>|expect_failure(r).stuff[0].1.clone();
+        let options = ParseOptions::default();
+        // ("s", <pointer to "s" read as a float>)
+        let mut memory = Memory::with_capacity(2, &options);
+        let msg = failure(block(|_,memory,i| {
+            let (i,_) = String::parse_ref(memory, i)?;
+            let (i,_) = float(memory, i)?;
+            Ok((i,()))
+        })(&mut memory, &[0xa0, 0x21, b's', CODE_SHARED8, 1]).map(|(i,_)|(i,())));
+        assert_eq!(msg, "Pointer 1 is to object 1, which was read as String but is wanted here as f64");
+        let mut memory = Memory::with_capacity(0, &options);
+        memory.expected = Some(3);
+        let msg = failure(float(&mut memory, &[CODE_SHARED8, 2]).map(|(i,_)|(i,())));
+        assert_eq!(msg, "Pointer 2 is to before the start of the segment, only 0 objects so far (the header declares 3)");
+        let msg = failure(float(&mut memory, &[CODE_SHARED8, 0]).map(|(i,_)|(i,())));
+        assert_eq!(msg, "Pointer 0 would be to object 0, which hasn't been read yet (the header declares 3)");
+    }
+
+    #[test]
+    fn cyclic_value_in_typed_parser() {
+        // let rec l = 1 :: l, read as a pair
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(1, &options);
+        let e = expect_failure(tuple2(int, tuple2(int, int))(&mut memory, &[0xa0, 0x41, 0x04, 0x01]));
+        assert!(e.stuff[0].1.starts_with("Pointer 1 is to cell 0, a block that contains it: the value is cyclic"));
+    }
+
+    #[test]
+    fn bools() {
+        let options = ParseOptions::default();
+        let mut memory = Memory::with_capacity(1, &options);
+        let (_,v) = Vec::<bool>::parse_val(&mut memory, &[0xa0, 0x41, 0x40]).unwrap();
+        assert_eq!(v, vec![true, false]);
+        assert!(bool_val(&mut memory, &[0x42]).is_err());
+        assert!(bool_val(&mut memory, &[0x90, 0x41]).is_err());
+    }
+
+    #[test]
+    fn object_iter_stops_after_error() {
+        let data = [0x41, CODE_INFIXPOINTER, 0x41];
+        let objects: Vec<_> = ObjectIter::new(&data).collect();
+        assert_eq!(objects.len(), 2);
+        assert!(objects[1].is_err());
+    }
 }
@@ -0,0 +1,181 @@
+// Untyped parsing: any marshalled data as a tree of values, for exploring segments that have no
+// typed parser yet. Shared objects become shared Rcs.
+
+use std::rc::Rc;
+
+use crate::parse::{marshal_data,ObjectIter,Repr,E};
+use crate::split::split_segments;
+use crate::types::{BigInt,DigestBytes,Uint63};
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum Value {
+    Int(i64),
+    Block{tag: u8, fields: Vec<Rc<Value>>},
+    String(Vec<u8>),
+    Double(f64),
+    Int63(Uint63),
+    Int32(i32),
+    Int64(i64),
+    NativeInt(i64),
+    BigInt(BigInt),
+    Custom{ident: String, payload: Vec<u8>},
+    CodePointer{offset: u32, digest: DigestBytes},
+    // A pointer to a block that contains it, making the value cyclic as OCaml allows.
+    // 0 is the innermost enclosing block, 1 the one around that, and so on.
+    Cycle(usize)
+}
+
+// A block still waiting for some of its fields, and its cell in the shared-object table
+struct Frame {
+    tag: u8,
+    size: usize,
+    fields: Vec<Rc<Value>>,
+    cell: usize
+}
+
+// The value encoded by some marshalled data (without its header), which must use all of it.
+// Works without recursion, since OCaml lists nest one block per element. Pointers back to a
+// block that is still being read become Value::Cycle.
+pub fn value(data: &[u8]) -> Result<Rc<Value>,E> {
+    let mut table: Vec<Option<Rc<Value>>> = vec![];
+    let mut stack: Vec<Frame> = vec![];
+    let mut root = None;
+    for object in ObjectIter::new(data) {
+        let (offset,repr) = object?;
+        let here = &data[offset..];
+        if root.is_some() {
+            return Err(E::new(here, "Data continues after the value ends".to_string()));
+        }
+        let shared = |table: &mut Vec<Option<Rc<Value>>>, v: Value| {
+            let rc = Rc::new(v);
+            table.push(Some(rc.clone()));
+            rc
+        };
+        let mut v = match repr {
+            Repr::RBlock(tag,0) => Rc::new(Value::Block{tag, fields:vec![]}),
+            Repr::RBlock(tag,size) => {
+                stack.push(Frame{tag, size, fields:Vec::with_capacity(size.min(data.len() - offset)), cell:table.len()});
+                table.push(None);
+                continue;
+            }
+            Repr::RInt(n) => Rc::new(Value::Int(n)),
+            Repr::RCodePointer{offset,digest} => Rc::new(Value::CodePointer{offset, digest}),
+            Repr::RPointer(n) => match table.len().checked_sub(n).and_then(|k|table.get(k)) {
+                Some(Some(rc)) => rc.clone(),
+                Some(None) => {
+                    // only enclosing blocks are unfinished, so the cell is on the stack
+                    let cell = table.len() - n;
+                    let depth = stack.iter().rev().position(|f|f.cell == cell).unwrap();
+                    Rc::new(Value::Cycle(depth))
+                }
+                None => return Err(E::new(here, format!("Shared pointer {} is out of range", n)))
+            },
+            Repr::RString(s) => shared(&mut table, Value::String(s)),
+            Repr::RDouble(x) => shared(&mut table, Value::Double(x)),
+            Repr::RInt63(n) => shared(&mut table, Value::Int63(n)),
+            Repr::RInt32(n) => shared(&mut table, Value::Int32(n)),
+            Repr::RInt64(n) => shared(&mut table, Value::Int64(n)),
+            Repr::RNativeInt(n) => shared(&mut table, Value::NativeInt(n)),
+            Repr::RBigInt(n) => shared(&mut table, Value::BigInt(n)),
+            Repr::RCustom(ident,payload) => shared(&mut table, Value::Custom{ident, payload})
+        };
+        // hand the value to its parent, finishing every block that it completes
+        loop {
+            match stack.last_mut() {
+                None => {
+                    root = Some(v);
+                    break;
+                }
+                Some(frame) => {
+                    frame.fields.push(v);
+                    if frame.fields.len() < frame.size {
+                        break;
+                    }
+                    let frame = stack.pop().unwrap();
+                    v = Rc::new(Value::Block{tag:frame.tag, fields:frame.fields});
+                    table[frame.cell] = Some(v.clone());
+                }
+            }
+        }
+    }
+    root.ok_or_else(||E::new(&[], "Data ends before the value does".to_string()))
+}
+
+// The value in each segment of a .vo file, found by following the stop offsets
+pub fn file_values(contents: &[u8]) -> Vec<Result<Rc<Value>,E>> {
+    let segments = match split_segments(contents) {
+        Some((_,segments)) => segments,
+        None => return vec![]
+    };
+    segments.iter().map(|m| match marshal_data(m) {
+        Some((_,data)) => value(&data),
+        None => Err(E::new(m, "Bad marshal header".to_string()))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selftest_summary() {
+        let values = file_values(include_bytes!("../selftest.vo"));
+        assert_eq!(values.len(), 5);
+        assert_eq!(*values[1].as_ref().unwrap().as_ref(), Value::Int(1));
+        let summary = values[0].as_ref().unwrap();
+        let fields = match summary.as_ref() {
+            Value::Block{tag:0, fields} => fields,
+            v => panic!("expected a block, got {:?}", v)
+        };
+        assert_eq!(fields.len(), 3);
+        // name: Mathparse.Selftest, as the dirpath list ["Selftest"; "Mathparse"]
+        match fields[0].as_ref() {
+            Value::Block{tag:0, fields} => assert_eq!(*fields[0], Value::String(b"Selftest".to_vec())),
+            v => panic!("expected a block, got {:?}", v)
+        }
+    }
+
+    #[test]
+    fn sharing_and_errors() {
+        // (s, s) with s = "ab", the second a pointer to the first
+        let v = value(&[0xa0, 0x22, b'a', b'b', 0x04, 0x01]).unwrap();
+        match v.as_ref() {
+            Value::Block{fields, ..} => assert!(Rc::ptr_eq(&fields[0], &fields[1])),
+            v => panic!("expected a block, got {:?}", v)
+        }
+        assert!(value(&[0xa0, 0x41]).is_err());
+        assert!(value(&[0x41, 0x41]).is_err());
+        assert!(value(&[0x90, 0x04, 0x02]).is_err());
+    }
+
+    #[test]
+    fn cycles() {
+        // let rec l = 1 :: l
+        let v = value(&[0xa0, 0x41, 0x04, 0x01]).unwrap();
+        assert_eq!(*v, Value::Block{tag:0, fields:vec![Rc::new(Value::Int(1)), Rc::new(Value::Cycle(0))]});
+        // let rec x = ((x, "s"), 0), the pointer skipping over the inner block
+        let v = value(&[0xa0, 0xa0, 0x04, 0x02, 0x21, b's', 0x40]).unwrap();
+        let inner = match v.as_ref() {
+            Value::Block{fields, ..} => fields[0].clone(),
+            v => panic!("expected a block, got {:?}", v)
+        };
+        assert_eq!(*inner, Value::Block{tag:0, fields:vec![Rc::new(Value::Cycle(1)), Rc::new(Value::String(b"s".to_vec()))]});
+    }
+
+    #[test]
+    fn deep_list() {
+        // [0; 0; ...; 0] with 100000 elements
+        let mut data = vec![];
+        for _ in 0..100_000 {
+            data.extend_from_slice(&[0xa0, 0x40]);
+        }
+        data.push(0x40);
+        let mut v = value(&data).unwrap();
+        let mut len = 0;
+        while let Value::Block{fields, ..} = v.clone().as_ref() {
+            len += 1;
+            v = fields[1].clone();
+        }
+        assert_eq!(len, 100_000);
+    }
+}
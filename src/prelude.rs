@@ -0,0 +1,13 @@
+// What a crate defining its own typed .vo structures needs, in one import:
+//
+//     use mathparse::prelude::*;
+//
+//     #[derive(Debug,Clone,VoParse)]
+//     pub struct Summary { pub name: DirPath, pub imports: Vec<DirPath>, pub deps: Vec<(DirPath,DigestBytes)> }
+
+pub use nom::IResult;
+pub use vo_parse_derive::VoParse;
+
+pub use crate::parse::{E,Memory,ParseOptions,SemanticError,VoParseRef};
+pub use crate::parse::{array,block,bool_val,bytes,bytes_exact,char_val,fail,float,hashtbl,int,int32_val,int64_val,lazy_val,list,map,my,nativeint_val,nullable,opt,parse_segment,ranged_int,set,string,tagged_block,tuple2,uint63,unshare,variant,vec,wrapped};
+pub use crate::types::{ByteString,DigestBytes,DirPath,Ident,Int32,Int64,Lazy,List,NativeInt,Uint63};
@@ -0,0 +1,63 @@
+use std::ops::Range;
+
+use crate::parse::E;
+use crate::split::segment_ranges;
+
+// A standalone file made of the original magic number and just the given segment, with its stop
+// offset rewritten to match the new position
+pub fn extract_segment(contents: &[u8], range: Range<usize>) -> Vec<u8> {
+    let mut result = contents[..4].to_vec();
+    let new_stop = (4 + range.end - 16 - range.start) as i32;
+    result.extend_from_slice(&new_stop.to_be_bytes());
+    result.extend_from_slice(&contents[range.start + 4..range.end]);
+    result
+}
+
+// Offset in contents of the innermost error, which nom records first
+pub fn error_offset(contents: &[u8], error: &E) -> Option<usize> {
+    error.stuff.first().map(|(remaining,_)|contents.len() - remaining)
+}
+
+// The segment containing the error, as a standalone file. None if the error is in the magic number
+// or the segment framing is too broken to find it.
+pub fn repro(contents: &[u8], error: &E) -> Option<Vec<u8>> {
+    let offset = error_offset(contents, error)?;
+    let range = segment_ranges(contents).into_iter().find(|r|r.contains(&offset) || r.end == offset)?;
+    Some(extract_segment(contents, range))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::file;
+
+    fn parse_error(contents: &[u8]) -> E {
+        match file(contents) {
+            Err(nom::Err::Failure(e)) | Err(nom::Err::Error(e)) => e,
+            r => panic!("expected an error, got {:?}", r.map(|_|()))
+        }
+    }
+
+    #[test]
+    fn repro_of_a_later_segment() {
+        let mut damaged = include_bytes!("selftest.vo").to_vec();
+        let ranges = segment_ranges(&damaged);
+        // only the summary's objects are parsed, so damage the marshal header of segment 1, just
+        // after its stop offset
+        let segment = ranges[1].clone();
+        damaged[segment.start + 5] = 0;
+        let e = parse_error(&damaged);
+        let offset = error_offset(&damaged, &e).unwrap();
+        assert!(segment.contains(&offset));
+
+        let r = repro(&damaged, &e).unwrap();
+        assert_eq!(r.len(), 4 + segment.len());
+        assert_eq!(r[..4], damaged[..4]);
+        assert_eq!(r[4..8], ((4 + segment.len() - 16) as i32).to_be_bytes());
+        assert_eq!(r[8..], damaged[segment.start + 4..segment.end]);
+        // the segment is now at offset 4, and fails where it did before
+        let re = parse_error(&r);
+        assert_eq!(error_offset(&r, &re).unwrap() - 4, offset - segment.start);
+        assert_eq!(re.stuff[0].1, e.stuff[0].1);
+    }
+}
@@ -0,0 +1,39 @@
+use crate::parse::file;
+
+// A tiny hand-built .vo (Coq 8.9 layout) for library Mathparse.Selftest
+const SAMPLE: &[u8] = include_bytes!("selftest.vo");
+
+fn check(what: &str, actual: String, expected: &str) -> Result<(),String> {
+    if actual == expected {
+        Ok(())
+    } else {
+        Err(format!("{}: expected {}, got {}", what, expected, actual))
+    }
+}
+
+// Parses the embedded sample and compares against what we know it contains
+pub fn selftest() -> Result<(),String> {
+    let vo = match file(SAMPLE) {
+        Ok((_,vo)) => vo,
+        Err(e) => return Err(format!("sample failed to parse: {:?}", e))
+    };
+    check("library name", vo.summary.name().to_string(), "Mathparse.Selftest")?;
+    let imports: Vec<String> = vo.summary.imports().iter().map(|d|d.to_string()).collect();
+    check("imports", imports.join(","), "Coq.Init.Prelude")?;
+    let deps: Vec<String> = vo.summary.deps().iter().map(|(d,digest)|format!("{}={}", d, digest.to_hex())).collect();
+    check("deps", deps.join(","), "Coq.Init.Prelude=000102030405060708090a0b0c0d0e0f,Coq.Init.Logic=101112131415161718191a1b1c1d1e1f")?;
+    check("segment count", vo.segments.len().to_string(), "5")?;
+    let bad = vo.bad_digests(SAMPLE);
+    if !bad.is_empty() {
+        return Err(format!("digest mismatch in segments {:?}", bad));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn selftest_passes() {
+        super::selftest().unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+use crate::parse::VoFile;
+
+// A snapshot is SNAPSHOT_MAGIC, the schema version as a little-endian u32, then the VoFile in bincode.
+// Bump SNAPSHOT_VERSION whenever VoFile or anything it contains changes shape, so that old snapshots
+// are rejected instead of being misread.
+pub const SNAPSHOT_MAGIC: &[u8;8] = b"MPSNAP\0\0";
+pub const SNAPSHOT_VERSION: u32 = 3;
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    NotASnapshot,
+    WrongVersion(u32),
+    Corrupt(String)
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+        match self {
+            SnapshotError::NotASnapshot => write!(f, "Not a mathparse snapshot"),
+            SnapshotError::WrongVersion(v) => write!(f, "Snapshot has schema version {}, expected {}", v, SNAPSHOT_VERSION),
+            SnapshotError::Corrupt(msg) => write!(f, "Corrupt snapshot: {}", msg)
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+pub fn save_snapshot(vo: &VoFile) -> Vec<u8> {
+    let mut result = SNAPSHOT_MAGIC.to_vec();
+    result.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+    bincode::serialize_into(&mut result, vo).unwrap();
+    result
+}
+
+pub fn load_snapshot(bytes: &[u8]) -> Result<VoFile,SnapshotError> {
+    if bytes.len() < 12 || &bytes[..8] != SNAPSHOT_MAGIC {
+        return Err(SnapshotError::NotASnapshot);
+    }
+    let mut version = [0;4];
+    version.copy_from_slice(&bytes[8..12]);
+    let version = u32::from_le_bytes(version);
+    if version != SNAPSHOT_VERSION {
+        return Err(SnapshotError::WrongVersion(version));
+    }
+    bincode::deserialize(&bytes[12..]).map_err(|e|SnapshotError::Corrupt(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::file;
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let (_,vo) = file(include_bytes!("selftest.vo")).unwrap();
+        let snapshot = save_snapshot(&vo);
+        let loaded = load_snapshot(&snapshot).unwrap();
+        assert_eq!(loaded.magic, vo.magic);
+        assert_eq!(loaded.summary.name().to_string(), vo.summary.name().to_string());
+        assert_eq!(loaded.summary.deps().len(), vo.summary.deps().len());
+        assert_eq!(loaded.library_digest(), vo.library_digest());
+
+        let mut old = snapshot.clone();
+        old[8] = 0;
+        assert!(matches!(load_snapshot(&old), Err(SnapshotError::WrongVersion(0))));
+        assert!(matches!(load_snapshot(b"garbage"), Err(SnapshotError::NotASnapshot)));
+        assert!(matches!(load_snapshot(&snapshot[..20]), Err(SnapshotError::Corrupt(_))));
+    }
+}
@@ -0,0 +1,76 @@
+use std::ops::Range;
+
+use nom::number::complete::be_i32;
+
+use crate::parse::{marshal_header,md5,HeaderKind};
+
+// Byte ranges of the segments (stop offset, marshal header and data, digest) following the 4-byte
+// magic number, found by following the stop offsets alone. Stops at the first offset that doesn't make sense.
+pub fn segment_ranges(contents: &[u8]) -> Vec<Range<usize>> {
+    let mut result = vec![];
+    let mut pos = 4;
+    while pos < contents.len() {
+        let stop = match be_i32::<(&[u8],nom::error::ErrorKind)>(&contents[pos..]) {
+            Ok((_,stop)) if stop >= 0 => stop as usize,
+            _ => break
+        };
+        let end = stop + 16;
+        if stop < pos + 4 || end > contents.len() {
+            break;
+        }
+        result.push(pos..end);
+        pos = end;
+    }
+    result
+}
+
+// The .vo magic number and each segment as a standalone marshal (header and data, as read by
+// OCaml's Marshal.from_channel). None if the file is too short to have a magic number.
+pub fn split_segments(contents: &[u8]) -> Option<(i32,Vec<&[u8]>)> {
+    if contents.len() < 4 {
+        return None;
+    }
+    let magic = i32::from_be_bytes([contents[0], contents[1], contents[2], contents[3]]);
+    let segments = segment_ranges(contents).into_iter().map(|r|&contents[r.start + 4..r.end - 16]).collect();
+    Some((magic, segments))
+}
+
+// Inverse of split_segments: lays the marshals out as a .vo, setting each header's data length
+// and recomputing the stop offsets and digests
+pub fn join_segments(magic: i32, segments: &[&[u8]]) -> Vec<u8> {
+    let mut result = magic.to_be_bytes().to_vec();
+    for segment in segments {
+        let stop = result.len() + 4 + segment.len();
+        result.extend_from_slice(&(stop as i32).to_be_bytes());
+        let start = result.len();
+        result.extend_from_slice(segment);
+        match marshal_header(segment) {
+            Some((header,data)) => {
+                match header.kind {
+                    HeaderKind::Small => result[start + 4..start + 8].copy_from_slice(&(data.len() as u32).to_be_bytes()),
+                    HeaderKind::Big => result[start + 8..start + 16].copy_from_slice(&(data.len() as u64).to_be_bytes()),
+                    // lengths are variable-length here, so changing one could move the data; leave it alone
+                    HeaderKind::Compressed => {}
+                }
+                result.extend_from_slice(&md5(data));
+            }
+            None => result.extend_from_slice(&md5(segment))
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &[u8] = include_bytes!("selftest.vo");
+
+    #[test]
+    fn split_then_join_is_identity() {
+        let (magic,segments) = split_segments(SAMPLE).unwrap();
+        assert_eq!(magic, 8991);
+        assert_eq!(segments.len(), 5);
+        assert_eq!(join_segments(magic, &segments), SAMPLE);
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fs::metadata;
+use std::io;
+use std::path::{Path,PathBuf};
+use std::time::UNIX_EPOCH;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::index::Index;
+use crate::parse::VoFile;
+use crate::types::{DigestBytes,DirPath};
+
+#[derive(Debug,Clone,Serialize,JsonSchema)]
+pub enum Staleness {
+    OlderThanSource(PathBuf),
+    DigestMismatch{dep: String, recorded: String, actual: String},
+    MissingDigest(String)
+}
+
+impl std::fmt::Display for Staleness {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+        match self {
+            Staleness::OlderThanSource(source) => write!(f, "older than its source {}", source.display()),
+            Staleness::DigestMismatch{dep,recorded,actual} => write!(f, "compiled against {} with digest {}, but it now has digest {}", dep, recorded, actual),
+            Staleness::MissingDigest(dep) => write!(f, "dependency {} has no library digest", dep)
+        }
+    }
+}
+
+#[derive(Debug,Clone,Serialize,JsonSchema)]
+pub struct StaleReport {
+    pub file: String,
+    pub problems: Vec<Staleness>
+}
+
+fn mtime(path: &Path) -> io::Result<u64> {
+    Ok(metadata(path)?.modified()?.duration_since(UNIX_EPOCH).map(|d|d.as_secs()).unwrap_or(0))
+}
+
+// Checks each indexed library against its .v source (if one sits next to it) and against the
+// digests of its dependencies that are also in the index. Dependencies outside the index are not checked.
+pub fn check_stale(dir: &Path, index: &Index) -> io::Result<Vec<StaleReport>> {
+    let digests: HashMap<&str,Option<&str>> = index.libraries.iter().map(|e|(e.name.as_str(),e.digest.as_deref())).collect();
+    let mut reports = vec![];
+    for entry in &index.libraries {
+        let mut problems = vec![];
+        let source = dir.join(&entry.file).with_extension("v");
+        if source.exists() && mtime(&source)? > entry.mtime {
+            problems.push(Staleness::OlderThanSource(source));
+        }
+        for dep in &entry.deps {
+            match digests.get(dep.name.as_str()) {
+                Some(Some(actual)) if *actual != dep.digest => {
+                    problems.push(Staleness::DigestMismatch{dep:dep.name.clone(), recorded:dep.digest.clone(), actual:actual.to_string()});
+                }
+                Some(None) => problems.push(Staleness::MissingDigest(dep.name.clone())),
+                _ => {}
+            }
+        }
+        if !problems.is_empty() {
+            reports.push(StaleReport{file:entry.file.clone(), problems});
+        }
+    }
+    Ok(reports)
+}
+
+// A dependency recorded by a library with a digest other than the named library's
+#[derive(Debug,Clone,Copy)]
+pub struct DepMismatch<'a> {
+    pub library: &'a VoFile,
+    pub dep: &'a DirPath,
+    pub recorded: &'a DigestBytes,
+    pub actual: &'a DigestBytes
+}
+
+// Checks every dependency recorded in a set of parsed libraries against the library digest of
+// the one it names, if that's in the set, and returns how many were checked. For running on every
+// rebuild: digests are compared in constant time and the only allocation is one table for the set.
+pub fn verify_deps<'a,F>(files: &'a [VoFile], mut mismatch: F) -> usize
+    where F:FnMut(DepMismatch<'a>)
+{
+    let digests: HashMap<&DirPath,&DigestBytes> = files.iter()
+        .filter_map(|vo|Some((vo.summary.name(), vo.library_digest()?)))
+        .collect();
+    let mut checked = 0;
+    for library in files {
+        for (dep,recorded) in library.summary.deps() {
+            if let Some(&actual) = digests.get(dep) {
+                checked += 1;
+                if !recorded.ct_eq(actual) {
+                    mismatch(DepMismatch{library, dep, recorded, actual});
+                }
+            }
+        }
+    }
+    checked
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{create_dir_all,remove_dir_all,write};
+
+    use super::*;
+    use crate::index::{IndexDep,IndexEntry};
+    use crate::parse::file;
+    use crate::types::{Ident,SummaryDisk};
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mathparse-{}-{}", name, std::process::id()));
+        let _ = remove_dir_all(&dir);
+        create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn entry(file: &str, name: &str, digest: Option<&str>, deps: &[(&str,&str)]) -> IndexEntry {
+        let deps = deps.iter().map(|(name,digest)|IndexDep{name:name.to_string(), digest:digest.to_string()}).collect();
+        IndexEntry{file:file.to_string(), name:name.to_string(), digest:digest.map(|d|d.to_string()), imports:vec![], deps, segments:vec![], size:0, mtime:0}
+    }
+
+    #[test]
+    fn stale_libraries() {
+        let dir = scratch_dir("stale");
+        // a.v is newer than the mtime recorded for a.vo; c.vo has no source next to it
+        write(dir.join("a.v"), b"").unwrap();
+        let mut b = entry("b.vo", "B", Some("bb"), &[("A", "aa"), ("Coq.Init", "ii")]);
+        b.mtime = u64::MAX;
+        write(dir.join("b.v"), b"").unwrap();
+        let index = Index{meta: None, libraries: vec![
+            entry("a.vo", "A", Some("a2"), &[]),
+            b,
+            entry("c.vo", "C", None, &[("B", "bb")]),
+            entry("d.vo", "D", None, &[("C", "cc")])
+        ]};
+        let reports: Vec<(String,Vec<String>)> = check_stale(&dir, &index).unwrap().iter()
+            .map(|r|(r.file.clone(), r.problems.iter().map(|p|p.to_string()).collect()))
+            .collect();
+        assert_eq!(reports, vec![
+            ("a.vo".to_string(), vec![format!("older than its source {}", dir.join("a.v").display())]),
+            ("b.vo".to_string(), vec!["compiled against A with digest aa, but it now has digest a2".to_string()]),
+            ("d.vo".to_string(), vec!["dependency C has no library digest".to_string()])
+        ]);
+        remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verified_deps() {
+        let base = file(include_bytes!("selftest.vo")).unwrap().1;
+        let name = |s: &str|DirPath::new(s.split('.').map(|p|Ident::new(p.as_bytes().to_vec())).collect());
+        let digest = base.library_digest().unwrap().clone();
+        let other = DigestBytes::new(&[0;16]);
+        let mut a = base.clone();
+        a.summary = SummaryDisk::new(name("A"), vec![], vec![]);
+        let mut b = base.clone();
+        b.summary = SummaryDisk::new(name("B"), vec![], vec![(name("A"), digest), (name("Coq.Init"), other.clone())]);
+        let mut c = base;
+        c.summary = SummaryDisk::new(name("C"), vec![], vec![(name("A"), other)]);
+        let files = vec![a, b, c];
+        let mut found = vec![];
+        assert_eq!(verify_deps(&files, |m|found.push((m.library.summary.name().to_string(), m.dep.to_string()))), 2);
+        assert_eq!(found, vec![("C".to_string(), "A".to_string())]);
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::{BTreeMap,HashMap};
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::parse::{code_name,marshal_data,ObjectIter,Repr};
+use crate::split::split_segments;
+
+// Counts of what a segment's marshalled data is made of. The histograms are indexed by bit length:
+// entry k counts values v with 2^(k-1) <= v < 2^k, and entry 0 counts zeros.
+#[derive(Debug,Clone,Default,PartialEq,Serialize,JsonSchema)]
+pub struct SegmentStats {
+    pub objects: usize,
+    pub code_counts: BTreeMap<String,usize>,
+    pub pointer_distances: Vec<usize>,
+    pub max_block_len: usize,
+    pub string_lengths: Vec<usize>,
+    // Set if decoding stopped early; the counts cover the objects before the error
+    pub error: Option<String>
+}
+
+fn bump(histogram: &mut Vec<usize>, value: usize) {
+    let k = (usize::BITS - value.leading_zeros()) as usize;
+    if histogram.len() <= k {
+        histogram.resize(k + 1, 0);
+    }
+    histogram[k] += 1;
+}
+
+// Statistics for some marshalled data, without its header
+pub fn segment_stats(data: &[u8]) -> SegmentStats {
+    let mut stats = SegmentStats::default();
+    for object in ObjectIter::new(data) {
+        let (offset,repr) = match object {
+            Ok(o) => o,
+            Err(e) => {
+                stats.error = e.stuff.first().map(|(_,msg)|msg.clone());
+                break;
+            }
+        };
+        stats.objects += 1;
+        *stats.code_counts.entry(code_name(data[offset]).to_string()).or_insert(0) += 1;
+        match repr {
+            Repr::RPointer(n) => bump(&mut stats.pointer_distances, n),
+            Repr::RBlock(_,len) => stats.max_block_len = stats.max_block_len.max(len),
+            Repr::RString(s) => bump(&mut stats.string_lengths, s.len()),
+            _ => {}
+        }
+    }
+    stats
+}
+
+// Statistics for each segment of a .vo file, found by following the stop offsets
+pub fn file_stats(contents: &[u8]) -> Vec<SegmentStats> {
+    let segments = match split_segments(contents) {
+        Some((_,segments)) => segments,
+        None => return vec![]
+    };
+    segments.iter().map(|m|marshal_data(m).map_or_else(SegmentStats::default, |(_,data)|segment_stats(&data))).collect()
+}
+
+// A string that is written out more than once instead of being shared
+#[derive(Debug,Clone,PartialEq,Serialize,JsonSchema)]
+pub struct DuplicateString {
+    pub string: String,
+    pub copies: usize,
+    // Bytes saved if each later copy were a pointer to the first
+    pub savings: usize
+}
+
+// How compactly the strings in a segment are encoded
+#[derive(Debug,Clone,Default,PartialEq,Serialize,JsonSchema)]
+pub struct StringReport {
+    pub strings: usize,
+    pub bytes: usize,
+    // Bytes saved by always using the shortest length encoding (short form, then STRING8, then STRING32)
+    pub wasted_encoding: usize,
+    // Largest savings first
+    pub duplicates: Vec<DuplicateString>,
+    pub error: Option<String>
+}
+
+fn minimal_string_size(len: usize) -> usize {
+    if len < 0x20 { 1 + len } else if len < 0x100 { 2 + len } else { 5 + len }
+}
+
+fn pointer_size(distance: usize) -> usize {
+    if distance < 0x100 { 2 } else if distance < 0x10000 { 3 } else { 5 }
+}
+
+pub fn string_report(data: &[u8]) -> StringReport {
+    let mut report = StringReport::default();
+    // first cell and copies so far, keyed by contents
+    let mut seen: HashMap<Vec<u8>,(usize,usize)> = HashMap::new();
+    let mut savings: HashMap<Vec<u8>,usize> = HashMap::new();
+    let mut cells = 0;
+    for object in ObjectIter::new(data) {
+        let (offset,repr) = match object {
+            Ok(o) => o,
+            Err(e) => {
+                report.error = e.stuff.first().map(|(_,msg)|msg.clone());
+                break;
+            }
+        };
+        match repr {
+            Repr::RString(s) => {
+                let size = match code_name(data[offset]) {
+                    "SMALL_STRING" => 1 + s.len(),
+                    "STRING8" => 2 + s.len(),
+                    _ => 5 + s.len()
+                };
+                report.strings += 1;
+                report.bytes += size;
+                report.wasted_encoding += size - minimal_string_size(s.len());
+                let entry = seen.entry(s.clone()).or_insert((cells,0));
+                entry.1 += 1;
+                if entry.1 > 1 {
+                    *savings.entry(s).or_insert(0) += size.saturating_sub(pointer_size(cells - entry.0));
+                }
+                cells += 1;
+            }
+            Repr::RInt(_) | Repr::RPointer(_) | Repr::RCodePointer{..} | Repr::RBlock(_,0) => {}
+            _ => cells += 1
+        }
+    }
+    report.duplicates = savings.into_iter().map(|(s,savings)|DuplicateString {
+        copies: seen[&s].1,
+        string: String::from_utf8_lossy(&s).into_owned(),
+        savings
+    }).collect();
+    report.duplicates.sort_by(|a,b|b.savings.cmp(&a.savings).then_with(||a.string.cmp(&b.string)));
+    report
+}
+
+pub fn file_string_reports(contents: &[u8]) -> Vec<StringReport> {
+    let segments = match split_segments(contents) {
+        Some((_,segments)) => segments,
+        None => return vec![]
+    };
+    segments.iter().map(|m|marshal_data(m).map_or_else(StringReport::default, |(_,data)|string_report(&data))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_and_histograms() {
+        // [| "Init"; <shared "Init">; 5 |] as a small block of 3 fields
+        let data = [0xb0, 0x24, b'I', b'n', b'i', b't', 0x04, 0x01, 0x45];
+        let stats = segment_stats(&data);
+        assert_eq!(stats.objects, 4);
+        assert_eq!(stats.code_counts["SMALL_BLOCK"], 1);
+        assert_eq!(stats.code_counts["SHARED8"], 1);
+        assert_eq!(stats.max_block_len, 3);
+        assert_eq!(stats.pointer_distances, vec![0, 1]);
+        assert_eq!(stats.string_lengths, vec![0, 0, 0, 1]);
+        assert_eq!(stats.error, None);
+    }
+
+    #[test]
+    fn wasted_bytes_and_duplicates() {
+        // ("Init" as STRING8, "Init" again in short form, "Coq")
+        let data = [0xb0, 0x09, 4, b'I', b'n', b'i', b't', 0x24, b'I', b'n', b'i', b't', 0x23, b'C', b'o', b'q'];
+        let report = string_report(&data);
+        assert_eq!(report.strings, 3);
+        assert_eq!(report.bytes, 6 + 5 + 4);
+        assert_eq!(report.wasted_encoding, 1);
+        assert_eq!(report.duplicates, vec![DuplicateString{string:"Init".to_string(), copies:2, savings:3}]);
+    }
+}
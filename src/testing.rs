@@ -0,0 +1,212 @@
+// Builds marshalled values for tests, instead of writing out the bytes by hand:
+//
+//     block(0).field(int(3)).field(str("x")).field(shared(1)).to_bytes()
+//
+// Each value is written in its shortest encoding. shared(n) is a pointer n objects back, as in
+// the marshal format itself, so the builder doesn't check that it points anywhere sensible.
+
+use crate::split::join_segments;
+
+#[derive(Debug,Clone,PartialEq)]
+pub enum ValueBuilder {
+    Int(i64),
+    Str(Vec<u8>),
+    Double(f64),
+    Block{tag: u8, fields: Vec<ValueBuilder>},
+    Shared(usize)
+}
+
+pub fn int(n: i64) -> ValueBuilder {
+    ValueBuilder::Int(n)
+}
+
+pub fn str(s: &str) -> ValueBuilder {
+    ValueBuilder::Str(s.as_bytes().to_vec())
+}
+
+pub fn bytes(s: &[u8]) -> ValueBuilder {
+    ValueBuilder::Str(s.to_vec())
+}
+
+pub fn double(x: f64) -> ValueBuilder {
+    ValueBuilder::Double(x)
+}
+
+// A block with no fields yet; add them with field()
+pub fn block(tag: u8) -> ValueBuilder {
+    ValueBuilder::Block{tag, fields: vec![]}
+}
+
+pub fn shared(n: usize) -> ValueBuilder {
+    ValueBuilder::Shared(n)
+}
+
+// The OCaml list of the given values
+pub fn list(items: Vec<ValueBuilder>) -> ValueBuilder {
+    items.into_iter().rev().fold(int(0), |tail,head|block(0).field(head).field(tail))
+}
+
+// None, or Some x
+pub fn option(x: Option<ValueBuilder>) -> ValueBuilder {
+    match x {
+        Some(x) => block(0).field(x),
+        None => int(0)
+    }
+}
+
+impl ValueBuilder {
+    pub fn block(tag: u8) -> Self {
+        block(tag)
+    }
+
+    // Adds a field to a block. Panics if this isn't one.
+    pub fn field(self, value: ValueBuilder) -> Self {
+        match self {
+            ValueBuilder::Block{tag, mut fields} => {
+                fields.push(value);
+                ValueBuilder::Block{tag, fields}
+            }
+            v => panic!("field() on {:?}, which isn't a block", v)
+        }
+    }
+
+    pub fn fields(self, values: Vec<ValueBuilder>) -> Self {
+        values.into_iter().fold(self, |b,v|b.field(v))
+    }
+
+    // Adds a field pointing n objects back
+    pub fn shared(self, n: usize) -> Self {
+        self.field(shared(n))
+    }
+
+    // The marshalled data, without a header
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        self.write(&mut out);
+        out
+    }
+
+    // The marshalled data after a small header, as stored in a segment
+    pub fn to_marshal(&self) -> Vec<u8> {
+        let data = self.to_bytes();
+        let (objects,size32,size64) = self.counts();
+        let mut out = 0x8495A6BEu32.to_be_bytes().to_vec();
+        for n in &[data.len(), objects, size32, size64] {
+            out.extend_from_slice(&(*n as u32).to_be_bytes());
+        }
+        out.extend_from_slice(&data);
+        out
+    }
+
+    // Shared objects, and the heap words they take with 32-bit and 64-bit OCaml
+    fn counts(&self) -> (usize,usize,usize) {
+        match self {
+            ValueBuilder::Int(_) | ValueBuilder::Shared(_) => (0,0,0),
+            ValueBuilder::Str(s) => (1, 1 + (s.len() + 4) / 4, 1 + (s.len() + 8) / 8),
+            ValueBuilder::Double(_) => (1, 3, 2),
+            ValueBuilder::Block{fields, ..} if fields.is_empty() => (0,0,0),
+            ValueBuilder::Block{fields, ..} => fields.iter().map(|f|f.counts())
+                .fold((1, 1 + fields.len(), 1 + fields.len()), |(a,b,c),(x,y,z)|(a + x, b + y, c + z))
+        }
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            ValueBuilder::Int(n) => match *n {
+                0..=0x3f => out.push(0x40 + *n as u8),
+                -0x80..=0x7f => out.extend_from_slice(&[0x00, *n as i8 as u8]),
+                -0x8000..=0x7fff => {
+                    out.push(0x01);
+                    out.extend_from_slice(&(*n as i16).to_be_bytes());
+                }
+                -0x8000_0000..=0x7fff_ffff => {
+                    out.push(0x02);
+                    out.extend_from_slice(&(*n as i32).to_be_bytes());
+                }
+                _ => {
+                    out.push(0x03);
+                    out.extend_from_slice(&n.to_be_bytes());
+                }
+            },
+            ValueBuilder::Str(s) => {
+                if s.len() < 0x20 {
+                    out.push(0x20 + s.len() as u8);
+                } else if s.len() < 0x100 {
+                    out.extend_from_slice(&[0x09, s.len() as u8]);
+                } else {
+                    out.push(0x0a);
+                    out.extend_from_slice(&(s.len() as u32).to_be_bytes());
+                }
+                out.extend_from_slice(s);
+            }
+            ValueBuilder::Double(x) => {
+                out.push(0x0b);
+                out.extend_from_slice(&x.to_be_bytes());
+            }
+            ValueBuilder::Block{tag, fields} => {
+                if *tag < 0x10 && fields.len() < 8 {
+                    out.push(0x80 + tag + ((fields.len() as u8) << 4));
+                } else {
+                    out.push(0x08);
+                    out.extend_from_slice(&(((fields.len() as u32) << 10) | *tag as u32).to_be_bytes());
+                }
+                for f in fields {
+                    f.write(out);
+                }
+            }
+            ValueBuilder::Shared(n) => {
+                if *n < 0x100 {
+                    out.extend_from_slice(&[0x04, *n as u8]);
+                } else if *n < 0x10000 {
+                    out.push(0x05);
+                    out.extend_from_slice(&(*n as u16).to_be_bytes());
+                } else {
+                    out.push(0x06);
+                    out.extend_from_slice(&(*n as u32).to_be_bytes());
+                }
+            }
+        }
+    }
+}
+
+// A .vo file with the given magic number and one segment per value, with correct stop offsets
+// and digests
+pub fn vo_file(magic: i32, segments: &[ValueBuilder]) -> Vec<u8> {
+    let marshals: Vec<Vec<u8>> = segments.iter().map(|s|s.to_marshal()).collect();
+    let slices: Vec<&[u8]> = marshals.iter().map(|m|m.as_slice()).collect();
+    join_segments(magic, &slices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::marshal_header;
+    use crate::parse::generic::{value,Value};
+
+    #[test]
+    fn encodings() {
+        assert_eq!(block(0).field(int(3)).field(str("x")).shared(1).to_bytes(), vec![0xb0, 0x43, 0x21, b'x', 0x04, 0x01]);
+        assert_eq!(int(-1).to_bytes(), vec![0x00, 0xff]);
+        assert_eq!(int(0x1234).to_bytes(), vec![0x01, 0x12, 0x34]);
+        assert_eq!(int(1 << 40).to_bytes(), vec![0x03, 0, 0, 1, 0, 0, 0, 0, 0]);
+        assert_eq!(block(20).field(int(0)).to_bytes(), vec![0x08, 0, 0, 0x04, 0x14, 0x40]);
+        assert_eq!(list(vec![int(1), int(2)]).to_bytes(), vec![0xa0, 0x41, 0xa0, 0x42, 0x40]);
+        assert_eq!(bytes(&[b'y'; 40]).to_bytes()[..2], [0x09, 40]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let v = block(1).fields(vec![str("abc"), double(0.5), list(vec![int(-300)]), option(None), shared(3)]);
+        let marshal = v.to_marshal();
+        let (h,data) = marshal_header(&marshal).unwrap();
+        assert_eq!(h.objects, 4);
+        let parsed = value(data).unwrap();
+        let fields = match parsed.as_ref() {
+            Value::Block{tag:1, fields} => fields.clone(),
+            v => panic!("expected a block, got {:?}", v)
+        };
+        assert_eq!(*fields[0], Value::String(b"abc".to_vec()));
+        assert_eq!(*fields[1], Value::Double(0.5));
+        assert!(std::rc::Rc::ptr_eq(&fields[0], &fields[4]));
+    }
+}
@@ -0,0 +1,131 @@
+// Everything worth attaching to a bug report about a .vo file that doesn't parse, gathered into
+// one bundle: what the file says it is, where its segments are, and what each diagnostic makes of
+// the segment that failed.
+
+use std::ops::Range;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::locate::{locate_corruption,scan_errors,Corruption,ScanError};
+use crate::parse::{coq_version,file_with_options,layout,marshal_data,marshal_header,md5,ParseOptions};
+use crate::repro::error_offset;
+use crate::split::segment_ranges;
+use crate::stats::{segment_stats,SegmentStats};
+
+// Bytes shown on each side of the error in the hexdump, rounded out to whole lines
+const CONTEXT: usize = 64;
+
+#[derive(Debug,Clone,PartialEq,Serialize,JsonSchema)]
+pub struct SegmentEntry {
+    pub name: Option<String>,
+    pub range: Range<usize>,
+    pub digest_ok: bool
+}
+
+#[derive(Debug,Clone,PartialEq,Serialize,JsonSchema)]
+pub struct Triage {
+    pub file_len: usize,
+    pub magic: Option<i32>,
+    pub coq_version: Option<String>,
+    pub segments: Vec<SegmentEntry>,
+    // The parse error, innermost message first, and the file offset of the innermost one
+    pub error: Vec<String>,
+    pub error_offset: Option<usize>,
+    pub failing_segment: Option<usize>,
+    pub stats: Option<SegmentStats>,
+    pub hexdump: Vec<String>,
+    // What the lenient scan and corruption locator find across the whole file
+    pub problems: Vec<ScanError>,
+    pub corruption: Vec<Corruption>
+}
+
+fn digest_ok(contents: &[u8], range: &Range<usize>) -> bool {
+    match marshal_header(&contents[range.start + 4..range.end - 16]) {
+        Some((_,data)) => md5(data) == contents[range.end - 16..range.end],
+        None => false
+    }
+}
+
+fn hex_line(contents: &[u8], start: usize) -> String {
+    let bytes = &contents[start..contents.len().min(start + 16)];
+    let hex: Vec<String> = bytes.iter().map(|b|format!("{:02x}", b)).collect();
+    let ascii: String = bytes.iter().map(|&b|if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+    format!("{:08x}  {:<47}  |{}|", start, hex.join(" "), ascii)
+}
+
+// 16 bytes a line around offset, with the line holding it marked
+pub fn hexdump(contents: &[u8], offset: usize, note: &str) -> Vec<String> {
+    let first = offset.saturating_sub(CONTEXT) / 16 * 16;
+    let last = offset.saturating_add(CONTEXT).min(contents.len());
+    (first..last).step_by(16).map(|start| {
+        let line = hex_line(contents, start);
+        if (start..start + 16).contains(&offset) {
+            format!("{}  <- {:#x}: {}", line, offset, note)
+        } else {
+            line
+        }
+    }).collect()
+}
+
+// The bundle for a file that fails to parse with the given options, or None if it parses
+pub fn triage(contents: &[u8], options: &ParseOptions, max_errors: usize) -> Option<Triage> {
+    let (error,offset) = match file_with_options(options, contents) {
+        Ok(_) => return None,
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            let offset = error_offset(contents, &e);
+            (e.stuff.into_iter().map(|(_,msg)|msg).collect::<Vec<_>>(), offset)
+        }
+        Err(nom::Err::Incomplete(_)) => (vec!["unexpected end of file".to_string()], Some(contents.len()))
+    };
+    let magic = if contents.len() >= 4 { Some(i32::from_be_bytes([contents[0], contents[1], contents[2], contents[3]])) } else { None };
+    let names = magic.and_then(layout).map(|l|l.segments).unwrap_or(&[]);
+    let ranges = segment_ranges(contents);
+    let failing_segment = offset.and_then(|o|ranges.iter().position(|r|r.contains(&o) || r.end == o));
+    let stats = failing_segment
+        .and_then(|n|marshal_data(&contents[ranges[n].start + 4..ranges[n].end - 16]))
+        .map(|(_,data)|segment_stats(&data));
+    let note = error.first().cloned().unwrap_or_default();
+    Some(Triage{
+        file_len: contents.len(),
+        magic,
+        coq_version: magic.and_then(coq_version).map(|v|v.to_string()),
+        segments: ranges.iter().enumerate().map(|(n,r)|SegmentEntry{
+            name: names.get(n).map(|s|s.to_string()),
+            range: r.clone(),
+            digest_ok: digest_ok(contents, r)
+        }).collect(),
+        error,
+        error_offset: offset,
+        failing_segment,
+        stats,
+        hexdump: offset.map(|o|hexdump(contents, o, &note)).unwrap_or_default(),
+        problems: scan_errors(contents, max_errors),
+        corruption: locate_corruption(contents)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundle_for_a_damaged_file() {
+        let sample = include_bytes!("selftest.vo");
+        assert!(triage(sample, &ParseOptions::default(), 20).is_none());
+        // an unknown code at the first object of segment 0, which starts at 28; the parse error is
+        // reported just after the code byte
+        let mut damaged = sample.to_vec();
+        damaged[29] = 0x11;
+        let t = triage(&damaged, &ParseOptions::default(), 20).unwrap();
+        assert_eq!(t.coq_version.as_deref(), Some("8.9"));
+        assert_eq!(t.segments.len(), 5);
+        assert_eq!(t.segments[0].name.as_deref(), Some("summary"));
+        assert!(!t.segments[0].digest_ok && t.segments[1].digest_ok);
+        assert_eq!((t.error_offset, t.failing_segment), (Some(30), Some(0)));
+        assert_eq!(t.stats.unwrap().objects, 1);
+        assert!(t.hexdump[1].starts_with("00000010  ") && t.hexdump[1].ends_with(&format!("<- 0x1e: {}", t.error[0])));
+        assert_eq!(t.problems[0].offset, 29);
+        assert_eq!(t.corruption[0].range, 28..30);
+    }
+}
@@ -1,16 +1,15 @@
+use std::collections::{BTreeMap,BTreeSet,HashMap};
+use std::hash::Hash;
+use std::convert::TryFrom;
 use std::rc::Rc;
 
 use nom::IResult;
+use serde::{Deserialize,Serialize};
 
-use crate::parse::{Memory,SemanticError,E,string,fail,as_string,my,block2,block3,tuple2,nullable,vec,wrapped};
-use crate::parse::{VoParseRef,unshare};
+use crate::parse::{Memory,SemanticError,Utf8Policy,E,bool_val,bytes_exact,char_val,lazy_val,string,float,uint63,opt,map,set,hashtbl,int,int32_val,int64_val,nativeint_val,fail,as_string,my,unshare,block2,tuple2,nullable,list,array,wrapped};
+use crate::parse::VoParseRef;
 use vo_parse_derive::VoParse;
 
-#[derive(Clone,VoParse)]
-struct Foo {
-    foo: String
-}
-
 impl VoParseRef for String {
     fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
         string(|data| {
@@ -19,6 +18,166 @@ impl VoParseRef for String {
     }
 }
 
+// A string that needn't be UTF-8, kept exactly as it appears in the file
+#[derive(Clone,PartialEq,Eq,Hash,PartialOrd,Ord,Serialize,Deserialize)]
+pub struct ByteString(pub Vec<u8>);
+
+impl ByteString {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for ByteString {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+        write!(f, "b\"{}\"", self.0.iter().flat_map(|&b|std::ascii::escape_default(b)).map(char::from).collect::<String>())
+    }
+}
+
+impl VoParseRef for ByteString {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        string(|data|Ok(ByteString(data)))(memory,input)
+    }
+}
+
+impl VoParseRef for bool {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,b) = bool_val(memory,input)?;
+        Ok((i,Rc::new(b)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        bool_val(memory,input)
+    }
+}
+
+// An OCaml int in a smaller Rust integer type, failing rather than truncating
+fn ranged<'b,T:TryFrom<i64>>(memory: &mut Memory, input: &'b[u8], type_name: &str) -> IResult<&'b[u8],T,E> {
+    let (i,n) = int(memory,input)?;
+    match T::try_from(n) {
+        Ok(x) => Ok((i,x)),
+        Err(_) => fail(i, format!("value {} out of range for {}{}", n, type_name, memory.field_context()))
+    }
+}
+
+impl VoParseRef for i64 {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,n) = int(memory,input)?;
+        Ok((i,Rc::new(n)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        int(memory,input)
+    }
+}
+
+impl VoParseRef for i32 {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,n) = Self::parse_val(memory,input)?;
+        Ok((i,Rc::new(n)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        ranged(memory, input, "i32")
+    }
+}
+
+impl VoParseRef for u8 {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,n) = Self::parse_val(memory,input)?;
+        Ok((i,Rc::new(n)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        ranged(memory, input, "u8")
+    }
+}
+
+impl VoParseRef for u16 {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,n) = Self::parse_val(memory,input)?;
+        Ok((i,Rc::new(n)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        ranged(memory, input, "u16")
+    }
+}
+
+impl VoParseRef for u32 {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,n) = Self::parse_val(memory,input)?;
+        Ok((i,Rc::new(n)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        ranged(memory, input, "u32")
+    }
+}
+
+impl VoParseRef for usize {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,n) = Self::parse_val(memory,input)?;
+        Ok((i,Rc::new(n)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        ranged(memory, input, "usize")
+    }
+}
+
+// OCaml's boxed fixed-width integers, which are custom blocks rather than ints
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash,PartialOrd,Ord,Serialize,Deserialize)]
+pub struct Int32(pub i32);
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash,PartialOrd,Ord,Serialize,Deserialize)]
+pub struct Int64(pub i64);
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash,PartialOrd,Ord,Serialize,Deserialize)]
+pub struct NativeInt(pub i64);
+
+impl VoParseRef for Int32 {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,n) = Self::parse_val(memory,input)?;
+        Ok((i,Rc::new(n)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        let (i,n) = int32_val(memory,input)?;
+        Ok((i,Int32(n)))
+    }
+}
+
+impl VoParseRef for Int64 {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,n) = Self::parse_val(memory,input)?;
+        Ok((i,Rc::new(n)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        let (i,n) = int64_val(memory,input)?;
+        Ok((i,Int64(n)))
+    }
+}
+
+impl VoParseRef for NativeInt {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,n) = Self::parse_val(memory,input)?;
+        Ok((i,Rc::new(n)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        let (i,n) = nativeint_val(memory,input)?;
+        Ok((i,NativeInt(n)))
+    }
+}
+
+impl VoParseRef for char {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,c) = char_val(memory,input)?;
+        Ok((i,Rc::new(c)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        char_val(memory,input)
+    }
+}
+
+impl VoParseRef for f64 {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        float(memory,input)
+    }
+}
+
 impl<T:VoParseRef> VoParseRef for Rc<T> {
     fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
         let (i,rc) = T::parse_ref(memory, input)?;
@@ -31,7 +190,65 @@ impl<T:VoParseRef> VoParseRef for Rc<T> {
 
 impl<T:VoParseRef+'static> VoParseRef for Vec<T> {
     fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
-        vec(T::parse_val)(memory,input)
+        array(T::parse_val)(memory,input)
+    }
+}
+
+// An OCaml list, where Vec is an OCaml array
+#[derive(Debug,Clone,PartialEq,Eq,Hash,Serialize,Deserialize)]
+pub struct List<T>(pub Vec<T>);
+
+impl<T:VoParseRef+Clone+'static> VoParseRef for List<T> {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,v) = list(T::parse_val)(memory,input)?;
+        Ok((i, Rc::new(List(unshare(v)))))
+    }
+}
+
+// An OCaml Map or Set, read from its balanced tree
+impl<K:VoParseRef+Ord+'static,V:VoParseRef+'static> VoParseRef for BTreeMap<K,V> {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        map(K::parse_val, V::parse_val)(memory,input)
+    }
+}
+
+impl<K:VoParseRef+Ord+'static> VoParseRef for BTreeSet<K> {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        set(K::parse_val)(memory,input)
+    }
+}
+
+// An OCaml Hashtbl
+impl<K:VoParseRef+Eq+Hash+'static,V:VoParseRef+'static> VoParseRef for HashMap<K,V> {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        hashtbl(K::parse_val, V::parse_val)(memory,input)
+    }
+}
+
+// A forced OCaml Lazy.t
+#[derive(Debug,Clone,PartialEq,Eq,Hash,Serialize,Deserialize)]
+pub struct Lazy<T>(pub T);
+
+impl<T:VoParseRef+'static> VoParseRef for Lazy<T> {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,v) = lazy_val(T::parse_val)(memory,input)?;
+        Ok((i,Rc::new(Lazy(v))))
+    }
+}
+
+impl<T:VoParseRef+'static> VoParseRef for Option<T> {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let (i,v) = opt(T::parse_val)(memory,input)?;
+        Ok((i, Rc::new(v)))
+    }
+    fn parse_val<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Self,E> {
+        opt(T::parse_val)(memory,input)
+    }
+}
+
+impl<const N: usize> VoParseRef for [u8;N] {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        bytes_exact::<N>()(memory,input)
     }
 }
 
@@ -42,7 +259,7 @@ impl<T:VoParseRef+'static,U:VoParseRef+'static> VoParseRef for (T,U) {
 }
 
 
-#[derive(Clone)]
+#[derive(Clone,PartialEq,Eq,Serialize,Deserialize)]
 pub struct DigestBytes {
     bytes: [u8;16]
 }
@@ -51,7 +268,15 @@ impl DigestBytes {
     pub fn new(slice: &[u8]) -> Self {
         let mut bytes = [0;16];
         bytes.copy_from_slice(&slice[..16]);
-        DigestBytes{bytes:bytes}
+        DigestBytes{bytes}
+    }
+    pub fn to_hex(&self) -> String {
+        self.bytes.iter().map(|b|format!("{:02x}",b)).collect()
+    }
+    // Equality that takes the same time wherever the digests differ
+    pub fn ct_eq(&self, other: &DigestBytes) -> bool {
+        let diff = self.bytes.iter().zip(other.bytes.iter()).fold(0u8, |acc,(a,b)|acc | (a ^ b));
+        std::hint::black_box(diff) == 0
     }
 }
 
@@ -61,31 +286,206 @@ impl std::fmt::Debug for DigestBytes {
     }
 }
 
-fn my_utf8<'a,'b>(memory: &'a mut Memory, i: &'b[u8]) -> IResult<&'b[u8], String, E> {
-    my(string(|data| {
-        String::from_utf8(data).map_err(|e|SemanticError::new(format!("{:?}",e)))
-    }))(memory,i)
-}
-
 impl VoParseRef for DigestBytes {
     fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
         wrapped(my_digest)(memory,input)
     }
 }
 
-fn my_digest<'a,'b>(memory: &'a mut Memory, i: &'b[u8]) -> IResult<&'b[u8], DigestBytes, E> {
-    my(string(|data| {
-        if data.len() == 16 {
-            Ok(DigestBytes::new(&data))
+fn my_digest<'b>(memory: &mut Memory, i: &'b[u8]) -> IResult<&'b[u8], DigestBytes, E> {
+    let (i,bytes) = my(bytes_exact::<16>())(memory,i)?;
+    Ok((i,DigestBytes{bytes}))
+}
+
+// A primitive integer (Coq's Uint63), below 2^63. Arithmetic wraps modulo 2^63, and as in Coq
+// dividing by zero gives zero and the remainder by zero is the dividend.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash,PartialOrd,Ord,Default,Serialize,Deserialize)]
+pub struct Uint63(u64);
+
+impl Uint63 {
+    pub const MAX: Uint63 = Uint63((1 << 63) - 1);
+    pub fn new(n: u64) -> Option<Self> {
+        if n <= Self::MAX.0 { Some(Uint63(n)) } else { None }
+    }
+    pub fn get(self) -> u64 {
+        self.0
+    }
+    // The low 63 bits, which is how 64-bit OCaml's tagged ints hold values of 2^62 and up
+    pub(crate) fn wrap(n: u64) -> Self {
+        Uint63(n & Self::MAX.0)
+    }
+}
+
+impl std::fmt::Display for Uint63 {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::ops::Add for Uint63 {
+    type Output = Uint63;
+    fn add(self, other: Uint63) -> Uint63 {
+        Uint63::wrap(self.0.wrapping_add(other.0))
+    }
+}
+
+impl std::ops::Sub for Uint63 {
+    type Output = Uint63;
+    fn sub(self, other: Uint63) -> Uint63 {
+        Uint63::wrap(self.0.wrapping_sub(other.0))
+    }
+}
+
+impl std::ops::Mul for Uint63 {
+    type Output = Uint63;
+    fn mul(self, other: Uint63) -> Uint63 {
+        Uint63::wrap(self.0.wrapping_mul(other.0))
+    }
+}
+
+impl std::ops::Div for Uint63 {
+    type Output = Uint63;
+    fn div(self, other: Uint63) -> Uint63 {
+        Uint63(self.0.checked_div(other.0).unwrap_or(0))
+    }
+}
+
+impl std::ops::Rem for Uint63 {
+    type Output = Uint63;
+    fn rem(self, other: Uint63) -> Uint63 {
+        Uint63(self.0.checked_rem(other.0).unwrap_or(self.0))
+    }
+}
+
+impl VoParseRef for Uint63 {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        uint63(memory,input)
+    }
+}
+
+// An arbitrary-precision integer as written by Zarith: a sign and the magnitude's bytes,
+// least significant first. With the num-bigint feature it converts to num_bigint::BigInt.
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct BigInt {
+    pub negative: bool,
+    pub magnitude: Vec<u8>
+}
+
+impl BigInt {
+    pub fn to_i128(&self) -> Option<i128> {
+        let significant = self.magnitude.iter().rposition(|&b|b != 0).map_or(0, |n|n + 1);
+        if significant > 16 {
+            return None;
+        }
+        let mut bytes = [0;16];
+        bytes[..significant].copy_from_slice(&self.magnitude[..significant]);
+        let n = u128::from_le_bytes(bytes);
+        if self.negative {
+            0i128.checked_sub_unsigned(n)
         } else {
-            SemanticError::msg(format!("digest: expected string of length 16, got {}", as_string(&data)))
+            i128::try_from(n).ok()
+        }
+    }
+}
+
+impl std::fmt::Display for BigInt {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+        // repeatedly divide the magnitude by 10^9, collecting the remainders
+        let mut limbs: Vec<u32> = self.magnitude.chunks(4).map(|c| {
+            let mut b = [0;4];
+            b[..c.len()].copy_from_slice(c);
+            u32::from_le_bytes(b)
+        }).collect();
+        let mut digits = vec![];
+        while limbs.iter().any(|&l|l != 0) {
+            let mut rem = 0u64;
+            for l in limbs.iter_mut().rev() {
+                let cur = (rem << 32) | *l as u64;
+                *l = (cur / 1_000_000_000) as u32;
+                rem = cur % 1_000_000_000;
+            }
+            digits.push(rem as u32);
+        }
+        match digits.split_last() {
+            None => write!(f, "0"),
+            Some((first,rest)) => {
+                if self.negative {
+                    write!(f, "-")?;
+                }
+                write!(f, "{}", first)?;
+                for d in rest.iter().rev() {
+                    write!(f, "{:09}", d)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+impl From<&BigInt> for num_bigint::BigInt {
+    fn from(n: &BigInt) -> Self {
+        let sign = if n.negative { num_bigint::Sign::Minus } else { num_bigint::Sign::Plus };
+        num_bigint::BigInt::from_bytes_le(sign, &n.magnitude)
+    }
+}
+
+// An identifier as the raw bytes found in the file, which Coq doesn't guarantee to be UTF-8
+#[derive(Clone,PartialEq,Eq,Hash,PartialOrd,Ord,Serialize,Deserialize)]
+pub struct Ident {
+    bytes: Vec<u8>
+}
+
+impl Ident {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Ident{bytes}
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+    pub fn to_str(&self) -> Result<&str,std::str::Utf8Error> {
+        std::str::from_utf8(&self.bytes)
+    }
+    pub fn to_string_lossy(&self) -> std::borrow::Cow<'_,str> {
+        String::from_utf8_lossy(&self.bytes)
+    }
+    fn checked(bytes: Vec<u8>, policy: Utf8Policy) -> Result<Self,SemanticError> {
+        match (policy, std::str::from_utf8(&bytes)) {
+            (_, Ok(_)) | (Utf8Policy::Preserve, _) => Ok(Ident{bytes}),
+            (Utf8Policy::Warn, Err(_)) => {
+                warn!("Identifier is not valid UTF-8: {}", as_string(&bytes));
+                Ok(Ident{bytes})
+            }
+            (Utf8Policy::Error, Err(e)) => SemanticError::msg(format!("Identifier {} is not valid UTF-8: {}", as_string(&bytes), e))
         }
-    }))(memory,i)
+    }
 }
 
-#[derive(Clone)]
+impl std::fmt::Debug for Ident {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+        match self.to_str() {
+            Ok(s) => write!(f, "{:?}", s),
+            Err(_) => write!(f, "Ident {:x?}", self.bytes)
+        }
+    }
+}
+
+impl std::fmt::Display for Ident {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+        write!(f, "{}", self.to_string_lossy())
+    }
+}
+
+impl VoParseRef for Ident {
+    fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
+        let policy = memory.options().utf8;
+        string(move|data|Ident::checked(data, policy))(memory,input)
+    }
+}
+
+#[derive(Clone,PartialEq,Eq,Hash,Serialize,Deserialize)]
 pub struct DirPath {
-    segments: Vec<String>
+    segments: Vec<Ident>
 }
 
 impl std::fmt::Debug for DirPath {
@@ -94,11 +494,25 @@ impl std::fmt::Debug for DirPath {
     }
 }
 
+impl std::fmt::Display for DirPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(),std::fmt::Error> {
+        let strings: Vec<_> = self.segments.iter().map(|s|s.to_string_lossy()).collect();
+        write!(f, "{}", strings.join("."))
+    }
+}
+
 impl DirPath {
+    // From the outermost segment in, as it's written: Coq.Init.Logic is [Coq, Init, Logic]
+    pub fn new(segments: Vec<Ident>) -> Self {
+        DirPath{segments}
+    }
+    pub fn segments(&self) -> &[Ident] {
+        &self.segments
+    }
     fn empty() -> Self {
         DirPath{segments:vec![]}
     }
-    fn concat(&self, head: String) -> Self {
+    fn concat(&self, head: Ident) -> Self {
         let mut vec = Vec::with_capacity(self.segments.len() + 1);
         vec.extend_from_slice(&self.segments);
         vec.push(head);
@@ -108,22 +522,36 @@ impl DirPath {
 
 impl VoParseRef for DirPath {
     fn parse_ref<'b>(memory: &mut Memory, i: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
-        let (i,result) = nullable(block2(my_utf8,DirPath::parse_val,|s,d|Ok(d.concat(s))))(memory,i)?;
-        if result.is_some() {
-            Ok((i,result.unwrap()))
-        } else {
-            Ok((i,Rc::new(DirPath::empty())))
+        let (i,result) = nullable(block2(Ident::parse_val,DirPath::parse_val,|s,d|Ok(d.concat(s))))(memory,i)?;
+        match result {
+            Some(rc) => Ok((i,rc)),
+            None => Ok((i,Rc::new(DirPath::empty())))
         }
     }
 }
 
-#[derive(Debug,Clone,VoParse)]
+#[derive(Debug,Clone,VoParse,Serialize,Deserialize)]
 pub struct SummaryDisk {
     name: DirPath,
     imports: Vec<DirPath>,
     deps: Vec<(DirPath, DigestBytes)>
 }
 
+impl SummaryDisk {
+    pub fn new(name: DirPath, imports: Vec<DirPath>, deps: Vec<(DirPath, DigestBytes)>) -> Self {
+        SummaryDisk{name, imports, deps}
+    }
+    pub fn name(&self) -> &DirPath {
+        &self.name
+    }
+    pub fn imports(&self) -> &[DirPath] {
+        &self.imports
+    }
+    pub fn deps(&self) -> &[(DirPath, DigestBytes)] {
+        &self.deps
+    }
+}
+
 /*
 pub fn my_summary_disk<'a,'b>(memory: &'a mut Memory, i: &'b[u8]) -> IResult<&'b[u8], SummaryDisk, E> {
     my(block3(
@@ -0,0 +1,118 @@
+// Snapshots of the human-readable output, so that changes to it show up in review. Update them
+// with `cargo insta review` (or INSTA_UPDATE=always) after checking the new output is intended.
+
+use std::path::PathBuf;
+use std::process::{Command,Stdio};
+
+fn mathparse(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_mathparse"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        // so the meta block in JSON reports has a fixed creation time
+        .env("SOURCE_DATE_EPOCH", "0")
+        .args(args)
+        .output()
+        .unwrap();
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn sample() -> String {
+    PathBuf::from("src").join("selftest.vo").display().to_string()
+}
+
+#[test]
+fn summary() {
+    insta::assert_snapshot!(mathparse(&["-vv", &sample()]));
+}
+
+#[test]
+fn stats() {
+    insta::assert_snapshot!(mathparse(&["stats", &sample()]));
+}
+
+#[test]
+fn string_stats() {
+    insta::assert_snapshot!(mathparse(&["stats", "--strings", &sample()]));
+}
+
+#[test]
+fn parse_error() {
+    insta::assert_snapshot!(mathparse(&["--locate-corruption", "tests/crashes/shared_before_start.vo"]));
+}
+
+#[test]
+fn triage_bundle() {
+    insta::assert_snapshot!(mathparse(&["triage", "tests/crashes/shared_before_start.vo"]));
+}
+
+#[test]
+fn dump_segment() {
+    insta::assert_snapshot!(mathparse(&["dump", &sample()]));
+}
+
+#[test]
+fn dump_with_limits() {
+    insta::assert_snapshot!(mathparse(&["dump", &sample(), "--max-depth", "3", "--max-nodes", "12"]));
+}
+
+#[test]
+fn native_code() {
+    let dir = std::env::temp_dir().join(format!("mathparse-native-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let vo = dir.join("Selftest.vo");
+    std::fs::copy(sample(), &vo).unwrap();
+    let vo = vo.display().to_string();
+    // without a .coq-native directory, the library wasn't built with -native-compiler
+    assert!(mathparse(&["-v", &vo]).ends_with("checksums verified\n"));
+    std::fs::create_dir(dir.join(".coq-native")).unwrap();
+    let out = mathparse(&["-v", &vo]);
+    assert!(out.contains("NMathparse_Selftest.cmxs is missing"), "{}", out);
+    assert!(out.contains("native code MISSING"), "{}", out);
+    std::fs::write(dir.join(".coq-native").join("NMathparse_Selftest.cmxs"), b"").unwrap();
+    assert!(mathparse(&["-v", &vo]).ends_with("checksums verified, native code present\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn index_changes() {
+    let dir = std::env::temp_dir().join(format!("mathparse-index-changes-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("lib")).unwrap();
+    let path = |name: &str|dir.join(name).display().to_string();
+    mathparse(&["index", &path("lib"), "-o", &path("before.json")]);
+    std::fs::copy(sample(), dir.join("lib").join("Selftest.vo")).unwrap();
+    mathparse(&["index", &path("lib"), "-p", &path("before.json"), "-o", &path("after.json"), "--changes", &path("changes.json")]);
+    let changes: serde_json::Value = serde_json::from_slice(&std::fs::read(dir.join("changes.json")).unwrap()).unwrap();
+    assert_eq!(changes, serde_json::json!({"added": ["Mathparse.Selftest"], "removed": [], "modified": [], "failed": []}));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn closed_stdout() {
+    // like `mathparse schema graph | head -0`: the reader is gone before anything is written
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mathparse"))
+        .args(["schema", "graph"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+}
+
+#[test]
+fn unusable_files_are_errors() {
+    for args in [&["stats", "no/such.vo"][..], &["triage", "tests/crashes/shared_before_start.vo", "-o", "no/such/dir/out.json"], &["index", "no/such/dir"]] {
+        let output = Command::new(env!("CARGO_BIN_EXE_mathparse"))
+            .current_dir(env!("CARGO_MANIFEST_DIR"))
+            .args(args)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.starts_with("Error no/such"), "{:?}: {}", args, stdout);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        assert!(!String::from_utf8(output.stderr).unwrap().contains("panicked"), "{:?}", args);
+    }
+}
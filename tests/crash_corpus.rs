@@ -0,0 +1,26 @@
+// Inputs that used to panic the parser. Each must now be rejected (or accepted) without panicking.
+// To add a case, drop the file into tests/crashes/.
+
+use std::fs::{read,read_dir};
+use std::panic::catch_unwind;
+use std::path::PathBuf;
+
+use mathparse::parse::file;
+
+fn crash_inputs() -> Vec<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("crashes");
+    let mut paths: Vec<PathBuf> = read_dir(dir).unwrap().map(|e|e.unwrap().path()).collect();
+    paths.sort();
+    paths
+}
+
+#[test]
+fn crash_corpus_does_not_panic() {
+    let inputs = crash_inputs();
+    assert!(!inputs.is_empty());
+    let panicked: Vec<String> = inputs.iter().filter(|path| {
+        let contents = read(path).unwrap();
+        catch_unwind(||{ let _ = file(&contents); }).is_err()
+    }).map(|path|path.display().to_string()).collect();
+    assert!(panicked.is_empty(), "parser panicked on {:?}", panicked);
+}
@@ -0,0 +1,28 @@
+// The .vo files that 'mathparse fixtures' compiled from tests/fixtures/src, one directory per Coq
+// version under tests/fixtures/vo. Each must still parse as recorded in its expected.json.
+// 8.9 holds src/selftest.vo until a Coq 8.9 is at hand to compile the sources.
+
+use std::fs::{read_dir,File};
+use std::path::PathBuf;
+
+use mathparse::corpus::{Corpus,run_corpus};
+
+#[test]
+fn fixtures_match_expectations() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("vo");
+    let versions = match read_dir(&dir) {
+        Ok(entries) => entries.map(|e|e.unwrap().path()).filter(|p|p.is_dir()).collect::<Vec<_>>(),
+        Err(_) => vec![]
+    };
+    if versions.is_empty() {
+        eprintln!("skipped: no fixture directories in {}, run 'mathparse fixtures' to make some", dir.display());
+        return;
+    }
+    let mut differences = vec![];
+    for version in versions {
+        let expected: Corpus = serde_json::from_reader(File::open(version.join("expected.json")).unwrap()).unwrap();
+        let comparison = run_corpus(&version).unwrap().compare(&expected);
+        differences.extend(comparison.differences.iter().map(|d|format!("{}: {}", version.display(), d)));
+    }
+    assert!(differences.is_empty(), "{:#?}", differences);
+}
@@ -0,0 +1,7 @@
+Inductive color := Red | Green | Blue.
+
+Definition next (c : color) : color :=
+  match c with Red => Green | Green => Blue | Blue => Red end.
+
+Lemma next_thrice : forall c, next (next (next c)) = c.
+Proof. destruct c; reflexivity. Qed.
@@ -0,0 +1,2 @@
+(* mathparse: since 8.13 *)
+Primitive array := #array_type.
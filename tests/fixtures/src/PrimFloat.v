@@ -0,0 +1,3 @@
+(* mathparse: since 8.11 *)
+Primitive float := #float64_type.
+Primitive add : float -> float -> float := #float64_add.
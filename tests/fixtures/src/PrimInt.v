@@ -0,0 +1,3 @@
+(* mathparse: since 8.10 *)
+Primitive int := #int63_type.
+Primitive add : int -> int -> int := #int63_add.
@@ -0,0 +1,30 @@
+(* Reads one marshalled value (header and data) from the file named on the command line and
+   prints it in preorder, one line per node, with sharing expanded. Used by tests/ocaml_diff.rs. *)
+
+let buf = Buffer.create 4096
+
+let rec dump v =
+  let r = Obj.repr v in
+  if Obj.is_int r then Printf.bprintf buf "I %d\n" (Obj.obj r : int)
+  else begin
+    let tag = Obj.tag r in
+    if tag = Obj.string_tag then begin
+      Buffer.add_string buf "S ";
+      String.iter (fun c -> Printf.bprintf buf "%02x" (Char.code c)) (Obj.obj r : string);
+      Buffer.add_char buf '\n'
+    end else if tag = Obj.custom_tag then
+      Printf.bprintf buf "J %Ld\n" (Obj.obj r : int64)
+    else if tag = Obj.double_tag then
+      Printf.bprintf buf "D %Ld\n" (Int64.bits_of_float (Obj.obj r : float))
+    else begin
+      Printf.bprintf buf "B %d %d\n" tag (Obj.size r);
+      for k = 0 to Obj.size r - 1 do dump (Obj.field r k) done
+    end
+  end
+
+let () =
+  let ic = open_in_bin Sys.argv.(1) in
+  let s = really_input_string ic (in_channel_length ic) in
+  close_in ic;
+  dump (Marshal.from_string s 0);
+  print_string (Buffer.contents buf)
@@ -0,0 +1,104 @@
+// Differential test against OCaml's own Marshal. Skipped unless MATHPARSE_OCAML names the `ocaml`
+// toplevel, e.g. MATHPARSE_OCAML=ocaml cargo test --test ocaml_diff.
+// Every segment of the sample inputs is dumped both by tests/ocaml/dump_marshal.ml and from the
+// untyped parse::generic::value tree here, with sharing expanded, and the two dumps must agree.
+// Inputs that one side rejects must be rejected by the other too.
+
+use std::env;
+use std::fs::{read,read_dir,remove_file,write};
+use std::path::PathBuf;
+use std::process::Command;
+
+use mathparse::parse::generic::{value,Value};
+use mathparse::parse::marshal_data;
+use mathparse::split::split_segments;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b|format!("{:02x}", b)).collect()
+}
+
+// The value in the same format as dump_marshal.ml, or None if the OCaml side can't print it.
+// Walks an explicit stack, since OCaml lists nest one block per element.
+fn dump(root: &Value) -> Option<String> {
+    let mut out = String::new();
+    let mut todo = vec![root];
+    while let Some(v) = todo.pop() {
+        match v {
+            Value::Int(n) => out.push_str(&format!("I {}\n", n)),
+            // integer custom blocks, which the OCaml side prints as int64
+            Value::Int63(n) => out.push_str(&format!("J {}\n", n.get() as i64)),
+            Value::Int32(n) => out.push_str(&format!("J {}\n", n)),
+            Value::Int64(n) | Value::NativeInt(n) => out.push_str(&format!("J {}\n", n)),
+            Value::String(s) => out.push_str(&format!("S {}\n", hex(s))),
+            Value::Double(x) => out.push_str(&format!("D {}\n", x.to_bits() as i64)),
+            Value::Block{tag,fields} => {
+                out.push_str(&format!("B {} {}\n", tag, fields.len()));
+                todo.extend(fields.iter().rev().map(|f|f.as_ref()));
+            }
+            // Zarith integers need Zarith loaded, other custom blocks and code pointers can't be
+            // printed, and dump_marshal.ml doesn't terminate on cycles
+            Value::BigInt(_) | Value::Custom{..} | Value::CodePointer{..} | Value::Cycle(_) => return None
+        }
+    }
+    Some(out)
+}
+
+fn rust_dump(marshal: &[u8]) -> Option<String> {
+    let (_,data) = marshal_data(marshal)?;
+    let root = value(&data).ok()?;
+    dump(&root)
+}
+
+fn ocaml_dump(ocaml: &str, marshal: &[u8]) -> Option<String> {
+    let script = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join("ocaml").join("dump_marshal.ml");
+    let input = env::temp_dir().join(format!("mathparse-ocaml-diff-{}.bin", std::process::id()));
+    write(&input, marshal).unwrap();
+    let output = Command::new(ocaml).arg(&script).arg(&input).output().unwrap();
+    remove_file(&input).unwrap();
+    if output.status.success() {
+        Some(String::from_utf8(output.stdout).unwrap())
+    } else {
+        None
+    }
+}
+
+fn sample_inputs() -> Vec<PathBuf> {
+    let root = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let mut paths = vec![root.join("src").join("selftest.vo")];
+    paths.extend(read_dir(root.join("tests").join("crashes")).unwrap().map(|e|e.unwrap().path()));
+    paths.sort();
+    paths
+}
+
+#[test]
+fn selftest_dumps() {
+    let contents = read(PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src").join("selftest.vo")).unwrap();
+    let (_,segments) = split_segments(&contents).unwrap();
+    let dumps: Vec<String> = segments.iter().map(|m|rust_dump(m).unwrap()).collect();
+    assert!(dumps[0].starts_with("B 0 "));
+    assert_eq!(dumps[1], "I 1\n");
+}
+
+#[test]
+fn generic_values_agree_with_ocaml() {
+    let ocaml = match env::var("MATHPARSE_OCAML") {
+        Ok(ocaml) => ocaml,
+        Err(_) => return
+    };
+    let mut divergences = vec![];
+    for path in sample_inputs() {
+        let contents = read(&path).unwrap();
+        let segments = match split_segments(&contents) {
+            Some((_,segments)) => segments,
+            None => continue
+        };
+        for (n,marshal) in segments.iter().enumerate() {
+            let ours = rust_dump(marshal);
+            let theirs = ocaml_dump(&ocaml, marshal);
+            if ours != theirs {
+                divergences.push(format!("{} segment {}:\n  ours:   {:?}\n  ocaml:  {:?}", path.display(), n, ours, theirs));
+            }
+        }
+    }
+    assert!(divergences.is_empty(), "{}", divergences.join("\n"));
+}
@@ -0,0 +1,46 @@
+// The prelude is enough for a crate outside mathparse to define and parse its own structures
+
+use mathparse::prelude::*;
+
+#[derive(Debug,Clone,VoParse)]
+struct Summary {
+    name: DirPath,
+    imports: Vec<DirPath>,
+    deps: Vec<(DirPath,DigestBytes)>
+}
+
+#[test]
+fn derive_outside_the_crate() {
+    let contents = include_bytes!("../src/selftest.vo");
+    let segment = parse_segment(contents, 0, Summary::parse_val, &ParseOptions::default()).unwrap();
+    assert_eq!(segment.value.name.to_string(), "Mathparse.Selftest");
+    assert_eq!(segment.value.imports.iter().map(|d|d.to_string()).collect::<Vec<_>>(), vec!["Coq.Init.Prelude"]);
+    assert_eq!(segment.value.deps[1].1.to_hex(), "101112131415161718191a1b1c1d1e1f");
+}
+
+#[derive(Debug,Clone,VoParse)]
+struct Entry {
+    key: String,
+    count: u16,
+    tags: List<String>
+}
+
+#[test]
+fn derive_with_built_input() {
+    use mathparse::testing::{block,int,list,str,vo_file};
+    let entries = block(0)
+        .field(block(0).field(str("a")).field(int(1)).field(list(vec![str("x"), str("y")])))
+        .field(block(0).field(str("b")).field(int(2)).field(list(vec![])));
+    let segment = parse_segment(&vo_file(8991, &[entries]), 0, Vec::<Entry>::parse_val, &ParseOptions::default()).unwrap();
+    assert_eq!((segment.value[0].key.as_str(), segment.value[0].count), ("a", 1));
+    assert_eq!(segment.value[0].tags.0, vec!["x", "y"]);
+    assert!(segment.value[1].tags.0.is_empty());
+    let entries = block(0).field(block(0).field(str("b")).field(int(70000)).field(list(vec![])));
+    let contents = vo_file(8991, &[entries]);
+    let e = parse_segment(&contents, 0, Vec::<Entry>::parse_val, &ParseOptions::default()).unwrap_err();
+    assert_eq!(e.stuff[0].1, "value 70000 out of range for u16 at Entry.count");
+    // a value read after the struct isn't reported as one of its fields
+    let pair = block(0).field(block(0).field(str("b")).field(int(2)).field(list(vec![]))).field(int(70000));
+    let e = parse_segment(&vo_file(8991, &[pair]), 0, <(Entry,u16)>::parse_val, &ParseOptions::default()).unwrap_err();
+    assert_eq!(e.stuff[0].1, "value 70000 out of range for u16");
+}
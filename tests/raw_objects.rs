@@ -0,0 +1,20 @@
+// Walking marshalled data object by object from outside the crate, without any typed parser
+
+use mathparse::parse::{marshal_header,parse_object,ObjectIter,Repr};
+use mathparse::testing::{block,int,str};
+
+#[test]
+fn parse_object_outside_the_crate() {
+    let marshal = block(3).field(int(-2)).field(str("ab")).shared(1).to_marshal();
+    let (_,data) = marshal_header(&marshal).unwrap();
+    let (rest,first) = parse_object(data).unwrap();
+    assert!(matches!(first, Repr::RBlock(3,3)));
+    let (rest,second) = parse_object(rest).unwrap();
+    assert!(matches!(second, Repr::RInt(-2)));
+    let reprs: Vec<Repr> = ObjectIter::new(rest).map(|o|o.unwrap().1).collect();
+    match &reprs[..] {
+        [Repr::RString(s), Repr::RPointer(1)] => assert_eq!(s, b"ab"),
+        r => panic!("unexpected objects {:?}", r)
+    }
+    assert!(parse_object(&[0x08, 0, 0]).is_err());
+}
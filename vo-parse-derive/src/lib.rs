@@ -25,8 +25,14 @@ fn impl_vo_parse(ast: &syn::DeriveInput) -> TokenStream {
                         let fname = field.ident.as_ref().unwrap();
                         let ftype = &field.ty;
                         let varname = format_ident!("data_{}", fname);
+                        let enter = if length == 0 {
+                            quote!{ let saved = memory.enter_field(stringify!(#name), stringify!(#fname)); }
+                        } else {
+                            quote!{ memory.enter_field(stringify!(#name), stringify!(#fname)); }
+                        };
                         let fsyntax = quote!{
-                            let (i,#varname) = <#ftype>::parse_val(memory, i)?;
+                            #enter
+                            let (i,#varname) = <#ftype as ::mathparse::parse::VoParseRef>::parse_val(memory, i)?;
                         };
                         field_parsers.push(fsyntax);
                         let fsyntax = quote!{ #fname: #varname, };
@@ -40,16 +46,23 @@ fn impl_vo_parse(ast: &syn::DeriveInput) -> TokenStream {
         _ => panic!("Cannot only VoParse on struct, not enum")
     }
 
+    // Errors after the struct shouldn't be blamed on its last field
+    let leave = if length == 0 { quote!{} } else { quote!{ memory.leave_fields(saved); } };
+
+    // Paths are absolute so the derive works outside mathparse too, which names itself mathparse
     let gen = quote! {
-        impl crate::parse::VoParseRef for #name {
-            fn parse_ref<'b>(memory: &mut Memory, input: &'b[u8]) -> IResult<&'b[u8],Rc<Self>,E> {
-                crate::parse::block(move|len,memory,i| {
+        impl ::mathparse::parse::VoParseRef for #name {
+            fn parse_ref<'b>(memory: &mut ::mathparse::parse::Memory, input: &'b[u8])
+                -> ::mathparse::prelude::IResult<&'b[u8],::std::rc::Rc<Self>,::mathparse::parse::E>
+            {
+                ::mathparse::parse::block(move|len,memory,i| {
                     if len == #length {
                         #(#field_parsers)*
+                        #leave
                         let data = #name{ #(#field_initializers)* };
                         Ok((i,data))
                     } else {
-                        fail(i, format!("{}: expected block length was {}, actual block length was {}", stringify!(#name), #length, len))
+                        ::mathparse::parse::fail(i, format!("{}: expected block length was {}, actual block length was {}", stringify!(#name), #length, len))
                     }
                 })(memory,input)
             }